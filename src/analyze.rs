@@ -0,0 +1,148 @@
+use std::future::Future;
+use std::task::{Context,Poll};
+
+use noop_waker::noop_waker;
+use num::FromPrimitive;
+use serde::Serialize;
+use tch::nn::VarStore;
+use xorshift::Rng;
+
+use super::logic::{State,Action,Modifier,ACTION_NUM};
+use super::setting::ModifierParameter;
+use super::mcts::{MCTSContext,ActionVector,ActionAnalysis};
+use super::predictor::Predictor;
+use super::network::{NetworkType,create_network};
+use super::cache::WeightsCache;
+use super::risk::RiskMode;
+use super::seed::{derive_seed,new_rng};
+
+// ランダムプレイアウトがこのターンを超えたら打ち切ります
+const MAX_ROLLOUT_TURN : u32 = 100;
+
+// 評価器です。ネットワークが指定されなければランダムプレイアウトで評価します
+pub enum AnalyzeEvaluator {
+    Weights(String),                      // ローカルの重みファイル
+    Model(String),                        // GCS上のモデル名
+    Rollout(u32),                         // 一様な事前確率と、指定回数のランダムプレイアウトの平均
+}
+
+pub struct AnalyzeParameter {
+    pub mod_param : ModifierParameter,
+    pub state : State,
+    pub evaluator : AnalyzeEvaluator,
+    pub network_type : NetworkType,
+    pub mcts_simulation_num : u32,
+    pub risk_mode : RiskMode,
+    pub json : bool,
+    pub seed : u64,
+}
+
+#[derive(Serialize)]
+struct AnalyzeResult<'a> {
+    state : &'a State,
+    simulations : u32,
+    actions : Vec<ActionAnalysis>,
+}
+
+fn legal_actions(s:&State, mod_param:&ModifierParameter) -> Vec<Action> {
+    (0..ACTION_NUM).map(|a| Action::from_usize(a).unwrap()).filter(|a| s.check_action(mod_param, a)).collect()
+}
+
+fn rollout(s:&State, modifier:&mut Modifier, risk_mode:&RiskMode) -> f32 {
+    let mut s = s.clone();
+    while !s.is_terminated() {
+        let actions = legal_actions(&s, &modifier.mod_param);
+        if actions.is_empty() || s.turn > MAX_ROLLOUT_TURN {
+            return 0.0
+        }
+        let a = actions[modifier.rng.next_u32() as usize % actions.len()];
+        s = s.run_action(modifier, &a);
+    }
+    risk_mode.terminal_value(&s, &modifier.mod_param)
+}
+
+fn evaluate_by_rollout(s:&State, modifier:&mut Modifier, risk_mode:&RiskMode, rollouts:u32) -> (ActionVector,f32) {
+    let mut policy = [0.0;ACTION_NUM];
+    let actions = legal_actions(s, &modifier.mod_param);
+    for a in &actions {
+        policy[*a as usize] = 1.0 / actions.len() as f32;
+    }
+
+    let value = (0..rollouts).map(|_| rollout(s, modifier, risk_mode)).sum::<f32>() / std::cmp::max(rollouts,1) as f32;
+    (policy, value)
+}
+
+// 探索のFutureを、予測が溜まるたびに評価しながら完了まで回します
+fn run_search<F:Future<Output=ActionVector>, P:FnMut()>(future:F, mut predict:P) -> ActionVector {
+    let waker = noop_waker();
+    let mut ctx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+
+    loop {
+        if let Poll::Ready(x) = future.as_mut().poll(&mut ctx) {
+            return x
+        }
+        predict();
+    }
+}
+
+fn load_local_weights(path:&str, network_type:NetworkType) -> (NetworkType,VarStore) {
+    let mut vs = VarStore::new(tch::Device::Cpu);
+    let _ = create_network(&vs.root(), network_type);
+    vs.load(path).unwrap();
+    (network_type,vs)
+}
+
+fn print_analysis(result:&AnalyzeResult) {
+    println!("simulations:{}", result.simulations);
+    println!("アクション\tN\tQ\tP\t読み筋");
+    for x in &result.actions {
+        let pv : Vec<&str> = x.pv.iter().map(|a| a.translate_ja()).collect();
+        println!("{}\t{}\t{:.4}\t{:.4}\t{}", x.action.translate_ja(), x.n, x.q, x.p, pv.join(" > "));
+    }
+}
+
+pub fn run_analyze( param:AnalyzeParameter ) {
+    let graph_name = match &param.evaluator {
+        AnalyzeEvaluator::Weights(x) | AnalyzeEvaluator::Model(x) => x.clone(),
+        AnalyzeEvaluator::Rollout(_) => "rollout".to_string(),
+    };
+
+    let mut predictor = Predictor::new();
+    match &param.evaluator {
+        AnalyzeEvaluator::Weights(path) => predictor.load_network(graph_name.clone(), &load_local_weights(path, param.network_type)),
+        AnalyzeEvaluator::Model(name) => predictor.load_network(graph_name.clone(), &*WeightsCache::new().load_weights(name, param.network_type).unwrap()),
+        AnalyzeEvaluator::Rollout(_) => {},
+    }
+
+    // 探索とプレイアウトは別の乱数系列にします
+    let mut modifier = Modifier { mod_param:param.mod_param.clone(), rng:new_rng(derive_seed(param.seed, 0)), forced_success:None };
+    let mut rollout_modifier = Modifier { mod_param:param.mod_param.clone(), rng:new_rng(derive_seed(param.seed, 1)), forced_success:None };
+
+    let mut context = MCTSContext::new(1.0, 0.15, 0.0, param.risk_mode, predictor.get_queue(), graph_name);
+    let search = context.search(&param.state, &mut modifier, param.mcts_simulation_num);
+
+    match &param.evaluator {
+        AnalyzeEvaluator::Rollout(rollouts) => {
+            let rollouts = *rollouts;
+            let risk_mode = param.risk_mode;
+            run_search(search, || predictor.predict_batch_with(|s| evaluate_by_rollout(s, &mut rollout_modifier, &risk_mode, rollouts)));
+        },
+        _ => {
+            run_search(search, || predictor.predict_batch(&param.mod_param));
+        },
+    }
+
+    let result = AnalyzeResult {
+        state : &param.state,
+        simulations : param.mcts_simulation_num,
+        actions : context.analyze(&param.state, &mut modifier),
+    };
+
+    if param.json {
+        println!("{}", serde_json::to_string_pretty(&result).unwrap());
+    }
+    else {
+        print_analysis(&result);
+    }
+}
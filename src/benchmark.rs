@@ -14,7 +14,7 @@ pub struct BenchmarkParameter {
 pub fn run_benchmark(param:BenchmarkParameter) {
 
     let vs = tch::nn::VarStore::new(tch::Device::Cpu);
-    let network = FullyConnectedNetwork::new(&vs.root(), 4, 128);
+    let network = FullyConnectedNetwork::new(&vs.root(), 4, 128, ValueHead::Scalar);
 
     let states : Vec<State> = (0..param.batch_size).map( |_| State::new(&param.mod_param) ).collect();
     let mut remain = param.plays_per_write;
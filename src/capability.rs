@@ -0,0 +1,120 @@
+use serde::{Serialize,Deserialize};
+
+use super::logic::Action;
+
+// 製作者の能力です。レベルとスペシャリストかどうかで使えるアクションと特性が決まります
+#[derive(Debug,Clone,Copy,PartialEq,Serialize,Deserialize)]
+pub struct Capability
+{
+    pub level : u32,
+    pub specialist : bool,
+}
+
+// アクションの習得レベル
+fn learned_level(a:&Action) -> u32 {
+    match *a {
+        Action::BasicSynthesis => 1,
+        Action::BasicTouch => 5,
+        Action::MastersMend => 7,
+        Action::HastyTouch => 9,
+        Action::RapidSynthesis => 9,
+        Action::Observe => 13,
+        Action::TricksOfTheTrade => 13,
+        Action::WasteNot => 15,
+        Action::Veneration => 15,
+        Action::StandardTouch => 18,
+        Action::GreatStrides => 21,
+        Action::Innovation => 26,
+        Action::FinalAppraisal => 42,
+        Action::WasteNot2 => 47,
+        Action::ByregotsBlessing => 50,
+        Action::PreciseTouch => 53,
+        Action::MuscleMemory => 54,
+        Action::CarefulObservation => 55,
+        Action::CarefulSynthesis => 62,
+        Action::Manipulation => 65,
+        Action::PrudentTouch => 66,
+        Action::FocusedSynthesis => 67,
+        Action::FocusedTouch => 68,
+        Action::Reflect => 69,
+        Action::PreparatoryTouch => 71,
+        Action::Groundwork => 72,
+        Action::DelicateSynthesis => 76,
+        Action::IntensiveSynthesis => 78,
+        Action::AdvancedTouch => 84,
+        Action::HeartAndSoul => 86,
+        Action::PrudentSynthesis => 88,
+        Action::TrainedFinesse => 90,
+        Action::RefinedTouch => 92,
+        Action::DaringTouch => 96,
+        Action::QuickInnovation => 96,
+        Action::ImmaculateMend => 98,
+        Action::TrainedPerfection => 100,
+    }
+}
+
+// スペシャリスト専用のアクション
+fn is_specialist_action(a:&Action) -> bool {
+    match *a {
+        Action::CarefulObservation | Action::HeartAndSoul | Action::QuickInnovation => true,
+        _ => false,
+    }
+}
+
+impl Capability {
+
+    // 全アクション・全特性が使える状態です
+    pub fn new_max() -> Capability {
+        Capability { level:100, specialist:true }
+    }
+
+    pub fn is_learned(&self, a:&Action) -> bool {
+        self.level >= learned_level(a) && (self.specialist || !is_specialist_action(a))
+    }
+
+    // 設計変更の回数
+    pub fn careful_observation(&self) -> u32 {
+        if self.is_learned(&Action::CarefulObservation) { 3 } else { 0 }
+    }
+
+    // 加工で増えるインナークワイエットのスタック数です。特性の習得前はスタックしません
+    // ビエルゴの祝福の効率はスタック数で決まるので、習得前は効率100%のままです
+    pub fn inner_quiet_stack(&self, stack:u32) -> u32 {
+        if self.level >= 11 { stack } else { 0 }
+    }
+
+    // 特性で強化される作業効率を、習得前の値に戻します
+    pub fn progress_efficiency(&self, a:&Action, efficiency:u32) -> u32 {
+        let (trait_level, base) = match *a {
+            Action::BasicSynthesis => (31, 100),   // 作業効率アップ
+            Action::RapidSynthesis => (63, 250),   // 突貫作業効率アップ
+            Action::CarefulSynthesis => (82, 150), // 模範作業効率アップ
+            Action::Groundwork => (86, 300),       // 下地作業効率アップ
+            Action::DelicateSynthesis => (94, 100), // 精密作業効率アップ
+            _ => return efficiency,
+        };
+
+        if self.level < trait_level { std::cmp::min(efficiency, base) } else { efficiency }
+    }
+}
+
+#[test]
+fn test_capability()
+{
+    let alt = Capability { level:90, specialist:false };
+    assert!( !alt.is_learned(&Action::CarefulObservation) );
+    assert!( !alt.is_learned(&Action::HeartAndSoul) );
+    assert!( alt.is_learned(&Action::TrainedFinesse) );
+    assert_eq!( 0, alt.careful_observation() );
+
+    let leveling = Capability { level:64, specialist:true };
+    assert!( !leveling.is_learned(&Action::Manipulation) );
+    assert_eq!( 150, leveling.progress_efficiency(&Action::CarefulSynthesis, 180) );
+    assert_eq!( 120, leveling.progress_efficiency(&Action::BasicSynthesis, 120) );
+    assert_eq!( 2, leveling.inner_quiet_stack(2) );
+    assert_eq!( 0, Capability { level:10, specialist:false }.inner_quiet_stack(2) );
+
+    // 精密作業は94で150%になります
+    assert_eq!( 100, Capability { level:93, specialist:true }.progress_efficiency(&Action::DelicateSynthesis, 150) );
+    assert_eq!( 150, Capability { level:94, specialist:true }.progress_efficiency(&Action::DelicateSynthesis, 150) );
+}
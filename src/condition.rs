@@ -0,0 +1,157 @@
+use serde::{Serialize,Deserialize};
+use xorshift::{Rng,Xorshift128};
+
+use super::logic::{State,Condition};
+
+// 状態の遷移モデルです。
+// レシピごとに状態の出現傾向が異なるため、ModifierParameterに持たせて切り替えます。
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub enum ConditionModel
+{
+    // 直前の状態に関係なく、毎回同じ表から選びます
+    Independent(Vec<(Condition,f32)>),
+
+    // 直前の状態ごとに表を持ちます。表にない状態の次は通常になります
+    Markov(Vec<(Condition,Vec<(Condition,f32)>)>),
+
+    // 2ターン目以降の状態を順に並べて強制します。並べたターンを過ぎたら後続のモデルに従います
+    Sequence(Vec<Condition>,Box<ConditionModel>),
+}
+
+// ゲームのルールで次の状態が決まっている場合はその状態を返します
+fn forced_condition(c:Condition) -> Option<Condition> {
+    match c {
+        Condition::GoodOmen => Some(Condition::HighQuality), // 好機の次は必ず高品質
+        Condition::Excellent => Some(Condition::Poor),       // 最高品質の次は必ず低品質
+        _ => None,
+    }
+}
+
+impl ConditionModel {
+
+    // Velvet Weissmelさんの統計データを参考に設定しています
+    // https://jp.finalfantasyxiv.com/lodestone/character/3514261/blog/4645845/
+    pub fn new_fountain_of_usouso() -> ConditionModel {
+        ConditionModel::Independent(vec![
+            (Condition::Standard, 0.37),
+            (Condition::HighQuality, 0.12),
+            (Condition::HighProgress, 0.12),
+            (Condition::Stable, 0.12),
+            (Condition::HighSustain, 0.12),
+            (Condition::Solid, 0.15),
+        ])
+    }
+
+    // イシュガルド第四次復興の高難度レシピの状態です。ウソウソの泉と違い高能率も出現します
+    // 出現率はウソウソの泉と同様の値を使い、残りを通常とします
+    pub fn new_ishgard_reconstruction_4th() -> ConditionModel {
+        ConditionModel::new_expert_recipe(&[
+            Condition::HighQuality,
+            Condition::HighProgress,
+            Condition::HighEfficiency,
+            Condition::HighSustain,
+            Condition::Solid,
+            Condition::Stable,
+        ])
+    }
+
+    // 通常レシピの状態です。高品質・最高品質の出現率は一般的に知られている値を使っています。
+    // 最高品質の次の低品質はゲームのルールとして強制されます
+    pub fn new_normal_recipe() -> ConditionModel {
+        ConditionModel::Independent(vec![
+            (Condition::Standard, 0.71),
+            (Condition::HighQuality, 0.25),
+            (Condition::Excellent, 0.04),
+        ])
+    }
+
+    // 高難度レシピの状態です。レシピで出現する状態(通常以外)を指定します。
+    // 出現率はウソウソの泉と同様の値を使い、残りを通常とします
+    pub fn new_expert_recipe(conditions:&[Condition]) -> ConditionModel {
+        let mut table : Vec<(Condition,f32)> = conditions.iter().filter(|c| **c != Condition::Standard).map(|c| {
+            let p = match c {
+                Condition::Solid | Condition::Stable | Condition::Robust => 0.15,
+                _ => 0.12,
+            };
+            (*c,p)
+        }).collect();
+
+        let rest = 1.0 - table.iter().map(|(_,p)| p).sum::<f32>();
+        table.insert(0, (Condition::Standard, rest.max(0.0)));
+        ConditionModel::Independent(table)
+    }
+
+    // 状態sの次の状態の確率分布を返します。
+    // sはターンを進めた後で、状態はまだ変化していないものを渡します
+    pub fn distribution(&self, s:&State) -> Vec<(Condition,f32)> {
+        if let Some(c) = forced_condition(s.condition) {
+            return vec![(c,1.0)]
+        }
+
+        match self {
+            ConditionModel::Independent(table) => table.clone(),
+            ConditionModel::Markov(rows) => {
+                match rows.iter().find(|(c,_)| *c == s.condition) {
+                    Some((_,table)) => table.clone(),
+                    None => vec![(Condition::Standard,1.0)],
+                }
+            },
+            ConditionModel::Sequence(conditions,rest) => {
+                match (s.turn as usize).checked_sub(2).and_then(|i| conditions.get(i)) {
+                    Some(c) => vec![(*c,1.0)],
+                    None => rest.distribution(s),
+                }
+            },
+        }
+    }
+
+    pub fn sample(&self, s:&State, rng:&mut Xorshift128) -> Condition {
+        let table = self.distribution(s);
+        let v = rng.next_f32();
+        let mut sum = 0.0;
+
+        for (c,p) in &table {
+            sum += p;
+            if v < sum {
+                return *c
+            }
+        }
+
+        // 数値誤差で総和が1.0に届かない場合は最後の状態とします
+        table.last().map(|(c,_)| *c).unwrap_or(Condition::Standard)
+    }
+}
+
+#[test]
+fn test_sequence_distribution()
+{
+    use super::setting::ModifierParameter;
+
+    let model = ConditionModel::Sequence(vec![Condition::HighQuality, Condition::GoodOmen], Box::new(ConditionModel::new_fountain_of_usouso()));
+    let s = State::new(&ModifierParameter::new_fountain_of_usouso());
+
+    assert_eq!( vec![(Condition::HighQuality,1.0)], model.distribution(&State { turn:2, .. s.clone() }) );
+    assert_eq!( vec![(Condition::GoodOmen,1.0)], model.distribution(&State { turn:3, .. s.clone() }) );
+    assert_eq!( vec![(Condition::HighQuality,1.0)], model.distribution(&State { turn:4, condition:Condition::GoodOmen, .. s.clone() }) );
+    assert_eq!( 6, model.distribution(&State { turn:4, .. s.clone() }).len() );
+}
+
+#[test]
+fn test_forced_condition()
+{
+    use xorshift::SeedableRng;
+    use super::setting::ModifierParameter;
+
+    // 好機の次はどの乱数でも高品質になります
+    let model = ConditionModel::new_fountain_of_usouso();
+    let s = State { condition:Condition::GoodOmen, .. State::new(&ModifierParameter::new_fountain_of_usouso()) };
+    let mut rng : Xorshift128 = SeedableRng::from_seed(&[1u64,2u64][..]);
+    for _ in 0..100 {
+        assert_eq!( Condition::HighQuality, model.sample(&s, &mut rng) );
+    }
+
+    // 第四次復興のモデルは高能率を含み、確率の総和は1です
+    let table = ConditionModel::new_ishgard_reconstruction_4th().distribution(&State { condition:Condition::Standard, .. s });
+    assert!( table.iter().any(|(c,p)| *c == Condition::HighEfficiency && *p > 0.0) );
+    assert!( (table.iter().map(|(_,p)| p).sum::<f32>() - 1.0).abs() < 1e-5 );
+}
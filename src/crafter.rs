@@ -0,0 +1,133 @@
+use serde::{Serialize,Deserialize};
+
+use super::setting::CrafterStatus;
+
+// 装備込みのジョブごとのステータスです。
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct JobStatus
+{
+    pub job : String,
+    pub level : u32,
+    pub craftsmanship : u32,
+    pub control : u32,
+    pub cp : u32,
+
+    #[serde(default)]
+    pub specialist : bool,
+}
+
+// 製作者のプロファイルです。ジョブごとに装備込みのステータスを持ちます
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct CrafterProfile
+{
+    pub name : String,
+    pub jobs : Vec<JobStatus>,
+}
+
+// 食事・薬の上昇量です。基本値のpercent%ですが、maxを超えません
+#[derive(Debug,Clone,Copy,Serialize,Deserialize)]
+pub struct Bonus
+{
+    pub percent : u32,
+    pub max : u32,
+}
+
+// 食事・薬です。
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct Consumable
+{
+    pub name : String,
+
+    #[serde(default)]
+    pub craftsmanship : Option<Bonus>,
+
+    #[serde(default)]
+    pub control : Option<Bonus>,
+
+    #[serde(default)]
+    pub cp : Option<Bonus>,
+}
+
+// プロファイルファイルの内容です。
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct CrafterCatalog
+{
+    pub profiles : Vec<CrafterProfile>,
+
+    #[serde(default)]
+    pub consumables : Vec<Consumable>,
+}
+
+impl Bonus {
+    pub fn apply(&self, base:u32) -> u32 {
+        std::cmp::min(base * self.percent / 100, self.max)
+    }
+}
+
+fn apply_bonus(base:u32, bonus:Option<Bonus>) -> u32 {
+    bonus.map(|b| b.apply(base)).unwrap_or(0)
+}
+
+impl JobStatus {
+
+    // 食事・薬込みのステータスを計算します。上昇量はどちらも装備込みの値を基準にします
+    pub fn effective_status(&self, consumables:&[&Consumable]) -> CrafterStatus {
+        let mut status = CrafterStatus {
+            craftsmanship : self.craftsmanship,
+            control : self.control,
+            cp : self.cp,
+            level : self.level,
+            specialist : self.specialist,
+        };
+
+        for c in consumables {
+            status.craftsmanship += apply_bonus(self.craftsmanship, c.craftsmanship);
+            status.control += apply_bonus(self.control, c.control);
+            status.cp += apply_bonus(self.cp, c.cp);
+        }
+
+        status
+    }
+}
+
+impl CrafterCatalog {
+
+    pub fn load(path:&str) -> Result<CrafterCatalog, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        serde_json::from_str(&text).map_err(|e| format!("can't parse {}: {}", path, e))
+    }
+
+    // "プロファイル名:ジョブ"の形式で検索します。ジョブを省略した場合は先頭のジョブになります
+    pub fn find_job(&self, key:&str) -> Result<&JobStatus, String> {
+        let mut xs = key.splitn(2, ':');
+        let name = xs.next().unwrap_or("");
+        let job = xs.next();
+
+        let profile = self.profiles.iter().find(|p| p.name == name).ok_or_else(|| format!("crafter {} is not found", name))?;
+        match job {
+            Some(job) => profile.jobs.iter().find(|j| j.job.eq_ignore_ascii_case(job)).ok_or_else(|| format!("job {} is not found in {}", job, name)),
+            None => profile.jobs.first().ok_or_else(|| format!("crafter {} has no job", name)),
+        }
+    }
+
+    pub fn find_consumable(&self, name:&str) -> Result<&Consumable, String> {
+        self.consumables.iter().find(|c| c.name == name).ok_or_else(|| format!("consumable {} is not found", name))
+    }
+}
+
+#[test]
+fn test_effective_status()
+{
+    let job = JobStatus { job:"CRP".to_string(), level:90, craftsmanship:3738, control:3768, cp:588, specialist:true };
+    let food = Consumable { name:"food".to_string(), craftsmanship:None, control:Some(Bonus { percent:5, max:90 }), cp:Some(Bonus { percent:26, max:78 }) };
+    let medicine = Consumable { name:"medicine".to_string(), craftsmanship:None, control:None, cp:Some(Bonus { percent:6, max:21 }) };
+
+    let status = job.effective_status(&[]);
+    assert_eq!( 588, status.cp );
+
+    // CPは上限まで、加工精度は5%の188が上限90で止まります
+    let status = job.effective_status(&[&food, &medicine]);
+    assert_eq!( 588 + 78 + 21, status.cp );
+    assert_eq!( 3768 + 90, status.control );
+    assert_eq!( 3738, status.craftsmanship );
+}
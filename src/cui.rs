@@ -1,11 +1,15 @@
-﻿
-use std::time::SystemTime;
-use super::logic::{Action,Modifier,State,Condition,get_technical_point};
+﻿use num::FromPrimitive;
+
+use super::logic::{Action,Modifier,State,Condition,IllegalReason,Event,ACTION_NUM};
 use super::setting::ModifierParameter;
-use xorshift::{SeedableRng};
+use super::seed::new_rng;
+use super::target::{Collectable,hq_percent};
+use super::macros::MacroLanguage;
 
 pub struct CuiParameter {
-    pub mod_param : ModifierParameter
+    pub mod_param : ModifierParameter,
+    pub initial_state : State,
+    pub seed : u64,
 }
 
 fn parse_action( cmd:&str ) -> Option<Action> {
@@ -42,6 +46,11 @@ fn parse_action( cmd:&str ) -> Option<Action> {
         "H" => Some(Action::HeartAndSoul),
         "I" => Some(Action::PrudentSynthesis),
         "J" => Some(Action::TrainedFinesse),
+        "K" => Some(Action::ImmaculateMend),
+        "L" => Some(Action::TrainedPerfection),
+        "M" => Some(Action::DaringTouch),
+        "N" => Some(Action::RefinedTouch),
+        "O" => Some(Action::QuickInnovation),
         _ => None,
     }
 }
@@ -81,8 +90,69 @@ impl Action {
             Action::HeartAndSoul => "一心不乱",
             Action::PrudentSynthesis => "倹約作業",
             Action::TrainedFinesse => "匠の神業",
+            Action::ImmaculateMend => "パーフェクトメンド",
+            Action::TrainedPerfection => "匠の絶技",
+            Action::DaringTouch => "デアリングタッチ",
+            Action::RefinedTouch => "洗練加工",
+            Action::QuickInnovation => "クイックイノベーション",
+        }
+    }
+
+    pub fn translate_en(&self) -> &'static str {
+        match *self {
+            Action::BasicSynthesis => "Basic Synthesis",
+            Action::BasicTouch => "Basic Touch",
+            Action::MastersMend => "Master's Mend",
+            Action::HastyTouch => "Hasty Touch",
+            Action::RapidSynthesis => "Rapid Synthesis",
+            Action::Observe => "Observe",
+            Action::TricksOfTheTrade => "Tricks of the Trade",
+            Action::WasteNot => "Waste Not",
+            Action::Veneration => "Veneration",
+            Action::StandardTouch => "Standard Touch",
+            Action::GreatStrides => "Great Strides",
+            Action::Innovation => "Innovation",
+            Action::FinalAppraisal => "Final Appraisal",
+            Action::WasteNot2 => "Waste Not II",
+            Action::ByregotsBlessing => "Byregot's Blessing",
+            Action::PreciseTouch => "Precise Touch",
+            Action::MuscleMemory => "Muscle Memory",
+            Action::CarefulObservation => "Careful Observation",
+            Action::CarefulSynthesis => "Careful Synthesis",
+            Action::Manipulation => "Manipulation",
+            Action::PrudentTouch => "Prudent Touch",
+            Action::FocusedSynthesis => "Focused Synthesis",
+            Action::FocusedTouch => "Focused Touch",
+            Action::Reflect => "Reflect",
+            Action::PreparatoryTouch => "Preparatory Touch",
+            Action::Groundwork => "Groundwork",
+            Action::DelicateSynthesis => "Delicate Synthesis",
+            Action::IntensiveSynthesis => "Intensive Synthesis",
+            Action::AdvancedTouch => "Advanced Touch",
+            Action::HeartAndSoul => "Heart and Soul",
+            Action::PrudentSynthesis => "Prudent Synthesis",
+            Action::TrainedFinesse => "Trained Finesse",
+            Action::ImmaculateMend => "Immaculate Mend",
+            Action::TrainedPerfection => "Trained Perfection",
+            Action::DaringTouch => "Daring Touch",
+            Action::RefinedTouch => "Refined Touch",
+            Action::QuickInnovation => "Quick Innovation",
+        }
+    }
+
+    pub fn translate(&self, lang:MacroLanguage) -> &'static str {
+        match lang {
+            MacroLanguage::English => self.translate_en(),
+            MacroLanguage::Japanese => self.translate_ja(),
         }
     }
+
+    // 英語名・日本語名のどちらからでも検索します。英語名は大文字小文字を区別しません
+    pub fn from_macro_name(name:&str) -> Option<Action> {
+        (0..ACTION_NUM)
+            .map(|i| Action::from_usize(i).unwrap())
+            .find(|a| a.translate_en().eq_ignore_ascii_case(name) || a.translate_ja() == name)
+    }
 }
 
 impl Condition {
@@ -95,6 +165,10 @@ impl Condition {
             Condition::HighSustain => "高持続",
             Condition::Solid => "頑丈",
             Condition::Stable => "安定",
+            Condition::GoodOmen => "好機",
+            Condition::Robust => "堅牢",
+            Condition::Excellent => "最高品質",
+            Condition::Poor => "低品質",
         }
     }
 
@@ -106,7 +180,31 @@ impl Condition {
             Condition::HighEfficiency => "\x1b[38;2;128;255;128m",
             Condition::HighSustain => "\x1b[38;2;255;255;128m",
             Condition::Solid => "\x1b[38;2;128;128;255m",
-            Condition::Stable => "\x1b[38;2;255;255;64m"
+            Condition::Stable => "\x1b[38;2;255;255;64m",
+            Condition::GoodOmen => "\x1b[38;2;255;192;64m",
+            Condition::Robust => "\x1b[38;2;64;192;255m",
+            Condition::Excellent => "\x1b[38;2;255;64;255m",
+            Condition::Poor => "\x1b[38;2;128;128;128m",
+        }
+    }
+}
+
+impl IllegalReason {
+    pub fn translate_ja(&self) -> String {
+        match *self {
+            IllegalReason::Unavailable => "このルールセットには存在しません".to_string(),
+            IllegalReason::NotLearned => "未習得です(レベル不足またはスペシャリスト専用)".to_string(),
+            IllegalReason::InsufficientCp(x) => format!("CPが足りません(必要CP:{})", x),
+            IllegalReason::WrongCondition => "状態が高品質・最高品質ではありません".to_string(),
+            IllegalReason::InnerQuietEmpty => "インナークワイエットがありません".to_string(),
+            IllegalReason::InnerQuietNotMax => "インナークワイエットが10ではありません".to_string(),
+            IllegalReason::TurnRestriction => "1ターン目しか使えません".to_string(),
+            IllegalReason::WasteNotActive => "倹約中は使えません".to_string(),
+            IllegalReason::HeartAndSoulUsed => "一心不乱は使用済みです".to_string(),
+            IllegalReason::AlreadyUsed => "使用済みです".to_string(),
+            IllegalReason::NoCarefulObservation => "設計変更の残り回数がありません".to_string(),
+            IllegalReason::NoExpedience => "ヘイスティタッチ成功直後ではありません".to_string(),
+            IllegalReason::InnovationActive => "イノベーション中は使えません".to_string(),
         }
     }
 }
@@ -186,6 +284,22 @@ fn print_state(s:&State,mod_param:&ModifierParameter) {
         println!("一心不乱再使用不可");
     }
 
+    if s.trained_perfection {
+        println!("匠の絶技有効");
+    }
+
+    if s.trained_perfection_used {
+        println!("匠の絶技再使用不可");
+    }
+
+    if s.quick_innovation_used {
+        println!("クイックイノベーション再使用不可");
+    }
+
+    if s.expedience {
+        println!("デアリングタッチ使用可能");
+    }
+
     if s.combo_observe {
         println!("注視作業/注視加工100%");
     }
@@ -199,24 +313,46 @@ fn print_state(s:&State,mod_param:&ModifierParameter) {
     }
 }
 
+// 直前のアクションで起きたことを表示します
+fn print_events(events:&[Event]) {
+    for e in events {
+        match e {
+            Event::Proc { success, probability } => println!("{}(成功率{}%)", if *success { "成功" } else { "失敗" }, (probability*100.0).round()),
+            Event::Progress { efficiency, amount } => println!("作業+{} (効率{}%)", amount, efficiency),
+            Event::Quality { efficiency, condition_rate, inner_quiet, innovation, great_strides, amount } => {
+                println!("品質+{} (効率{}% 状態{}% IQ{}{}{})", amount, efficiency, condition_rate, inner_quiet,
+                    if *innovation { " イノベーション" } else { "" }, if *great_strides { " グレートストライド" } else { "" });
+            },
+            Event::Durability { base, cost, lost } => println!("耐久-{} (消費{}→{})", lost, base, cost),
+            Event::ManipulationRegen(x) => println!("マニピュレーション耐久+{}", x),
+            Event::BuffExpired(x) => println!("{:?}終了", x),
+            Event::ConditionRolled(x) => println!("次の状態:{}", x.translate_ja()),
+            Event::FinalAppraisalTriggered => println!("最終確認発動"),
+        }
+    }
+}
+
 pub fn run_cui( param:CuiParameter ) {
-    let seed : u64 = From::from( SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).expect("Failed to get UNIXTIME").subsec_nanos() );
-    let states = [seed, seed];
-    let mut modifier = Modifier { mod_param:param.mod_param.clone(), rng:SeedableRng::from_seed(&states[..]) };
-    let mut state = State::new(&param.mod_param);
+    let mut modifier = Modifier { mod_param:param.mod_param.clone(), rng:new_rng(param.seed), forced_success:None };
+    let mut state = param.initial_state.clone();
+
+    let mut events = vec![];
 
     while !state.is_terminated() {
         print_state(&state, &param.mod_param);
+        print_events(&events);
 
         let mut cmd = String::new();
         std::io::stdin().read_line(&mut cmd).expect("Failed to read_line");
 
         if let Some(action) = parse_action(cmd.trim()) {
-            if state.check_action(&action) {
-                state = state.run_action(&mut modifier,&action)
-            }
-            else {
-                println!("Don't satisfy condition of [{:?}]", action);
+            match state.check_action_reason(&param.mod_param, &action) {
+                Ok(()) => {
+                    let (ns,xs) = state.run_action_with_events(&mut modifier,&action);
+                    state = ns;
+                    events = xs;
+                },
+                Err(reason) => println!("Don't satisfy condition of [{:?}]: {}", action, reason.translate_ja()),
             }
         }
         else {
@@ -225,10 +361,17 @@ pub fn run_cui( param:CuiParameter ) {
     }
 
     print_state(&state, &param.mod_param);
+    print_events(&events);
     if state.is_destroyed() {
-        println!("Destroyed => Technical Point:0" );
+        println!("Destroyed" );
     }
     else {
-        println!("Completed => Technical Point:{}", get_technical_point(state.quality/10) );
+        println!("Completed => HQ:{}%", hq_percent(state.quality, param.mod_param.max_quality) );
+        if let Some(x) = &param.mod_param.collectable {
+            println!("Collectability:{} Scrip:{}", Collectable::collectability(state.quality), x.scrip(state.quality) );
+        }
+        if let Some(x) = &param.mod_param.scoring {
+            println!("{}:{}", x.name, x.score(state.quality) );
+        }
     }
 }
@@ -3,10 +3,14 @@
 use super::logic::{State,Condition,ACTION_NUM};
 use super::setting::ModifierParameter;
 use super::mcts::*;
+use super::risk::{ValueHistogram,RISK_BINS,add_weighted_to_histogram};
 
-pub const STATE_NUM : usize = 36;
+pub const STATE_NUM : usize = 45;
 pub type StateVector = [f32;STATE_NUM];
 
+// 分布型バリューヘッドの点の数です。報酬[0,1]を等間隔に区切ります
+pub const VALUE_ATOMS : usize = 21;
+
 trait OneHotConvertible {
     fn to_onehot(&self) -> f32;
 }
@@ -27,7 +31,7 @@ impl OneHotConvertible for bool {
 pub fn encode_state( s:&State, mod_param:&ModifierParameter ) -> StateVector {
     [
         s.turn as f32 / 128.0,
-        s.time as f32 / 256.0,
+        s.time as f32 / 256000.0,
         s.completed.to_onehot(), // 要らない気がする
         s.working as f32 / mod_param.max_working as f32,
         s.quality as f32 / mod_param.max_quality as f32,
@@ -55,6 +59,10 @@ pub fn encode_state( s:&State, mod_param:&ModifierParameter ) -> StateVector {
 
         s.heart_and_soul.to_onehot(),
         s.heart_and_soul_used.to_onehot(),
+        s.trained_perfection.to_onehot(),
+        s.trained_perfection_used.to_onehot(),
+        s.quick_innovation_used.to_onehot(),
+        s.expedience.to_onehot(),
         s.combo_basic_touch.to_onehot(),
         s.combo_standard_touch.to_onehot(),
         s.combo_observe.to_onehot(),
@@ -62,11 +70,14 @@ pub fn encode_state( s:&State, mod_param:&ModifierParameter ) -> StateVector {
         (s.condition == Condition::Standard).to_onehot(),
         (s.condition == Condition::HighQuality).to_onehot(),
         (s.condition == Condition::HighProgress).to_onehot(),
-        //第四次の場合はこちらを使うけれども非対応です
-        //(s.condition == Condition::HighEfficiency).to_onehot(),
+        (s.condition == Condition::HighEfficiency).to_onehot(),
         (s.condition == Condition::HighSustain).to_onehot(),
         (s.condition == Condition::Solid).to_onehot(),
         (s.condition == Condition::Stable).to_onehot(),
+        (s.condition == Condition::GoodOmen).to_onehot(),
+        (s.condition == Condition::Robust).to_onehot(),
+        (s.condition == Condition::Excellent).to_onehot(),
+        (s.condition == Condition::Poor).to_onehot(),
     ]
 }
 
@@ -90,9 +101,85 @@ fn convert_to_policy_vector( t:&Tensor, offset:i64 ) -> ActionVector {
     res
 }
 
-pub fn decode_pv_batch( (policy_res_t,value_res_t):(Tensor,Tensor) ) -> Vec<(ActionVector,f32)> {
+// i番目の点の報酬です
+pub fn value_atom( i:usize ) -> f32 {
+    i as f32 / (VALUE_ATOMS - 1) as f32
+}
+
+// 報酬(N,1)を隣り合う2点に按分した分布(N,VALUE_ATOMS)にします。分布型バリューヘッドの教師データです
+pub fn encode_value_distribution_batch( values:&Tensor ) -> Tensor {
+    let max_index = (VALUE_ATOMS - 1) as f64;
+    let position = values.clamp(0.0, 1.0) * max_index;
+    let lower = position.floor();
+    let upper_weight = &position - &lower;
+    let upper = (&lower + 1.0).clamp_max(max_index);
+
+    let lower_onehot = lower.to_kind(Kind::Int64).one_hot(VALUE_ATOMS as i64).to_kind(Kind::Float).view(&[-1, VALUE_ATOMS as i64]);
+    let upper_onehot = upper.to_kind(Kind::Int64).one_hot(VALUE_ATOMS as i64).to_kind(Kind::Float).view(&[-1, VALUE_ATOMS as i64]);
+
+    lower_onehot * (1.0 - &upper_weight) + upper_onehot * upper_weight
+}
+
+// 分布型バリューヘッドの出力のi行目の分布です
+pub fn decode_value_distribution( value_res_t:&Tensor, i:i64 ) -> Vec<f32> {
+    (0..VALUE_ATOMS).map(|j| value_res_t.double_value(&[i,j as i64]) as f32).collect()
+}
+
+// 各点の確率を、MCTSのリスク指標で使うヒストグラムに振り分けます
+pub fn value_distribution_to_histogram( distribution:&[f32] ) -> ValueHistogram {
+    let mut h = [0.0;RISK_BINS];
+    for (j,p) in distribution.iter().enumerate() {
+        add_weighted_to_histogram(&mut h, value_atom(j), *p);
+    }
+    h
+}
+
+// 出力が1列ならそのまま、分布なら期待値を評価値にして、分布もヒストグラムで返します
+fn decode_value( value_res_t:&Tensor, i:i64 ) -> (f32,Option<ValueHistogram>) {
+    if value_res_t.size2().unwrap().1 == 1 {
+        (value_res_t.double_value(&[i,0]) as f32, None)
+    }
+    else {
+        let distribution = decode_value_distribution(value_res_t, i);
+        let mean = distribution.iter().enumerate().map(|(j,p)| p * value_atom(j)).sum();
+        (mean, Some(value_distribution_to_histogram(&distribution)))
+    }
+}
+
+pub fn decode_pv_batch( (policy_res_t,value_res_t):(Tensor,Tensor) ) -> Vec<Prediction> {
     let policy_iter = (0..policy_res_t.size2().unwrap().0).into_iter().map(|i| convert_to_policy_vector(&policy_res_t,i));
-    let value_iter = (0..value_res_t.size2().unwrap().0).into_iter().map(|i| value_res_t.double_value(&[i as i64,0]) as f32);
+    let value_iter = (0..value_res_t.size2().unwrap().0).into_iter().map(|i| decode_value(&value_res_t,i));
+
+    policy_iter.zip(value_iter).map(|(p,(v,h))| (p,v,h)).collect()
+}
+
+#[test]
+fn test_encode_condition()
+{
+    use super::logic::CONDITIONS;
+
+    // 状態は末尾のone-hotに1つずつ割り当てられ、互いに重なりません
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let offset = STATE_NUM - CONDITIONS.len();
+    for (i,c) in CONDITIONS.iter().enumerate() {
+        let v = encode_state(&State { condition:*c, .. State::new(&mod_param) }, &mod_param);
+        let expected : Vec<f32> = (0..CONDITIONS.len()).map(|j| if i == j { 1.0 } else { 0.0 }).collect();
+        assert_eq!( expected, v[offset..].to_vec(), "{:?}", c );
+    }
+}
 
-    policy_iter.zip(value_iter).collect()
+#[test]
+fn test_value_distribution_to_histogram()
+{
+    // 両端の点は両端のビンに入り、合計は評価1回分になります
+    let mut distribution = vec![0.0;VALUE_ATOMS];
+    distribution[0] = 0.25;
+    distribution[VALUE_ATOMS/2] = 0.25;
+    distribution[VALUE_ATOMS-1] = 0.5;
+
+    let h = value_distribution_to_histogram(&distribution);
+    assert_eq!( 0.25, h[0] );
+    assert_eq!( 0.25, h[RISK_BINS/2] );
+    assert_eq!( 0.5, h[RISK_BINS-1] );
+    assert_eq!( 1.0, h.iter().sum::<f32>() );
 }
@@ -12,6 +12,7 @@ use ulid::*;
 use super::gcs::*;
 use super::network::*;
 use super::logic::*;
+use super::encoding::encode_value_distribution_batch;
 
 pub struct LearnerParameter {
     pub epochs_per_write : usize,
@@ -106,8 +107,14 @@ fn loss_policy(p_pred:&Tensor, p_true:&Tensor) -> Tensor {
     (-p_true * (p_pred+0.0001).log()).sum_dim_intlist( &[1], false, Kind::Double ).mean(Kind::Double)
 }
 
+// 分布型バリューヘッドの場合は、報酬を按分した分布との交差エントロピーにします
 fn loss_value(v_pred:&Tensor, v_true:&Tensor) -> Tensor {
-    (v_pred - v_true).square().mean(Kind::Double)
+    if v_pred.size2().unwrap().1 == 1 {
+        (v_pred - v_true).square().mean(Kind::Double)
+    }
+    else {
+        loss_policy(v_pred, &encode_value_distribution_batch(v_true))
+    }
 }
 
 fn loss_alphazero(p_pred:&Tensor, p_true:&Tensor, v_pred:&Tensor, v_true:&Tensor) -> (Tensor,Tensor,Tensor) {
@@ -243,3 +250,32 @@ pub fn run( param:&LearnerParameter ) {
         run_epoch_loop( &mysql_pool, &mut record_buffer, &mut optimizer, &vs, &net, &param.network_type, param.epochs_per_write );
     }
 }
+
+#[test]
+fn test_categorical_value_loss()
+{
+    use super::setting::ModifierParameter;
+    use super::encoding::encode_state_batch;
+
+    // 報酬1.0の教師は最後の点のone-hotになり、学習すると交差エントロピーが下がります
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let states : Vec<State> = (0..4).map(|i| State { cp:100 + i * 50, .. State::new(&mod_param) }).collect();
+    let input = encode_state_batch(&states, &mod_param);
+    let v_true = Tensor::ones(&[4,1], (Kind::Float, Device::Cpu));
+
+    let vs = nn::VarStore::new(Device::Cpu);
+    let net = create_network(&vs.root(), NetworkType::FullyConnected(1, 32, ValueHead::Categorical));
+    let mut optimizer = nn::Adam::default().build(&vs, 1e-2).unwrap();
+
+    let eval_loss = |net:&Box<dyn DualNetwork>| {
+        let (_,v) = net.forward_t(&input, false);
+        f64::from(&loss_value(&v, &v_true))
+    };
+
+    let before = eval_loss(&net);
+    for _ in 0..20 {
+        let (_,v) = net.forward_t(&input, false);
+        optimizer.backward_step(&loss_value(&v, &v_true));
+    }
+    assert!( eval_loss(&net) < before );
+}
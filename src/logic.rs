@@ -11,16 +11,36 @@ use num::traits::{FromPrimitive,ToPrimitive};
 #[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize,Hash)]
 pub enum Condition
 {
-    Standard,       // 通常
-    HighQuality,    // 高品質
-    HighProgress,   // 高進捗
-    HighEfficiency, // 高能率
-    HighSustain,    // 高持続
-    Solid,          // 頑丈
-    Stable,         // 安定
+    Standard,       // 通常(Normal)
+    HighQuality,    // 高品質(Good)
+    HighProgress,   // 高進捗(Malleable)
+    HighEfficiency, // 高能率(Pliant)
+    HighSustain,    // 高持続(Primed)
+    Solid,          // 頑丈(Sturdy)
+    Stable,         // 安定(Centered)
+    GoodOmen,       // 好機(Good Omen)
+    Robust,         // 堅牢(Robust)
+    Excellent,      // 最高品質(Excellent)
+    Poor,           // 低品質(Poor)
 }
 
-pub const ACTION_NUM: usize = 32;
+pub const CONDITION_NUM: usize = 11;
+
+pub const CONDITIONS: [Condition;CONDITION_NUM] = [
+    Condition::Standard,
+    Condition::HighQuality,
+    Condition::HighProgress,
+    Condition::HighEfficiency,
+    Condition::HighSustain,
+    Condition::Solid,
+    Condition::Stable,
+    Condition::GoodOmen,
+    Condition::Robust,
+    Condition::Excellent,
+    Condition::Poor,
+];
+
+pub const ACTION_NUM: usize = 37;
 
 #[derive(Debug,Clone,Copy,Serialize,Deserialize,PartialEq,Eq,Hash)]
 pub enum Action {
@@ -56,13 +76,63 @@ pub enum Action {
     HeartAndSoul,       // 一心不乱
     PrudentSynthesis,   // 倹約作業
     TrainedFinesse,     // 匠の神業
+    ImmaculateMend,     // パーフェクトメンド
+    TrainedPerfection,  // 匠の絶技
+    DaringTouch,        // デアリングタッチ
+    RefinedTouch,       // 洗練加工
+    QuickInnovation,    // クイックイノベーション
+}
+
+// アクションが実行できない理由です。
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum IllegalReason {
+    Unavailable,            // ルールセットに存在しない
+    NotLearned,             // レベル不足・スペシャリスト専用
+    InsufficientCp(u32),    // CP不足(必要CP)
+    WrongCondition,         // 高品質・最高品質(または一心不乱)が必要
+    InnerQuietEmpty,        // インナークワイエットが0
+    InnerQuietNotMax,       // インナークワイエットが10ではない
+    TurnRestriction,        // 1ターン目限定
+    WasteNotActive,         // 倹約中は使えない
+    HeartAndSoulUsed,       // 一心不乱は使用済み
+    AlreadyUsed,            // 1回限りのアクションが使用済み
+    NoCarefulObservation,   // 設計変更の残り回数がない
+    NoExpedience,           // ヘイスティタッチ成功直後ではない
+    InnovationActive,       // イノベーション中は使えない
+}
+
+// 時間経過・消費で切れるバフです。
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Buff {
+    WasteNot,
+    Veneration,
+    GreatStrides,
+    Innovation,
+    FinalAppraisal,
+    MuscleMemory,
+    Manipulation,
+    HeartAndSoul,
+    TrainedPerfection,
+}
+
+// アクション実行時に起きた出来事です。
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub enum Event {
+    Proc { success:bool, probability:f32 },                  // 成否判定
+    Progress { efficiency:u32, amount:u32 },                 // 作業
+    Quality { efficiency:u32, condition_rate:u32, inner_quiet:u32, innovation:bool, great_strides:bool, amount:u32 }, // 加工(適用された補正)
+    Durability { base:u32, cost:u32, lost:u32 },             // 消費耐久(倹約・頑丈適用前、適用後、実際に減った値)
+    ManipulationRegen(u32),                                  // マニピュレーションによる回復
+    BuffExpired(Buff),                                       // バフ切れ・消費
+    ConditionRolled(Condition),                              // 次の状態
+    FinalAppraisalTriggered,                                 // 最終確認で完成が止められた
 }
 
 #[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,Hash)]
 pub struct State
 {
     pub turn : u32,                   // ターン
-    pub time : u32,                   // 推定経過時間
+    pub time : u32,                   // 推定経過時間(ミリ秒)
     pub completed: bool,              // 完成フラグ
     pub working : u32,                // 工数
     pub quality: u32,                 // 品質
@@ -79,6 +149,10 @@ pub struct State
     pub manipulation : u32,           // マニピュレーション
     pub heart_and_soul : bool,        // 一心不乱有効
     pub heart_and_soul_used : bool,   // 一心不乱を使用済みかどうか
+    pub trained_perfection : bool,    // 匠の絶技有効
+    pub trained_perfection_used : bool, // 匠の絶技を使用済みかどうか
+    pub quick_innovation_used : bool, // クイックイノベーションを使用済みかどうか
+    pub expedience : bool,            // 直前にヘイスティタッチが成功したかどうか
     pub combo_basic_touch : bool,     // 直前に加工したかどうか
     pub combo_standard_touch : bool,  // 直前に中級加工したかどうか
     pub combo_observe : bool,         // 直前に経過観察したかどうか
@@ -89,6 +163,7 @@ pub struct Modifier
 {
     pub mod_param : ModifierParameter,
     pub rng : Xorshift128,
+    pub forced_success : Option<bool>,    // 成否を乱数で決めずに固定する場合に指定します(結果の列挙用)
 }
 
 // https://totem3.hatenablog.jp/entry/2015/08/07/222303
@@ -138,6 +213,11 @@ impl FromPrimitive for Action {
             29 => Some(Action::HeartAndSoul),       // 一心不乱
             30 => Some(Action::PrudentSynthesis),   // 倹約作業
             31 => Some(Action::TrainedFinesse),     // 匠の神業
+            32 => Some(Action::ImmaculateMend),     // パーフェクトメンド
+            33 => Some(Action::TrainedPerfection),  // 匠の絶技
+            34 => Some(Action::DaringTouch),        // デアリングタッチ
+            35 => Some(Action::RefinedTouch),       // 洗練加工
+            36 => Some(Action::QuickInnovation),    // クイックイノベーション
             _ => None
         }
     }
@@ -182,13 +262,55 @@ impl ToPrimitive for Action {
             Action::HeartAndSoul => 29,
             Action::PrudentSynthesis => 30,
             Action::TrainedFinesse => 31,
+            Action::ImmaculateMend => 32,
+            Action::TrainedPerfection => 33,
+            Action::DaringTouch => 34,
+            Action::RefinedTouch => 35,
+            Action::QuickInnovation => 36,
         })
     }
 }
 
+impl Action {
+    // 待ち時間が短いバフ系のアクションです
+    pub fn is_buff(&self) -> bool {
+        match *self {
+            Action::MastersMend | Action::WasteNot | Action::Veneration | Action::GreatStrides | Action::Innovation |
+            Action::FinalAppraisal | Action::WasteNot2 | Action::CarefulObservation | Action::Manipulation |
+            Action::HeartAndSoul | Action::ImmaculateMend | Action::TrainedPerfection | Action::QuickInnovation => true,
+            _ => false,
+        }
+    }
+
+    // ゲーム内マクロで標準的に使う待ち時間です。バフ系は2秒、それ以外は3秒です
+    pub fn default_wait(&self) -> u32 {
+        if self.is_buff() { 2 } else { 3 }
+    }
+}
+
+impl Condition {
+    // 品質の状態補正(%)
+    pub fn quality_rate(&self) -> u32 {
+        match *self {
+            Condition::HighQuality => 150,
+            Condition::Excellent => 400,
+            Condition::Poor => 50,
+            _ => 100,
+        }
+    }
+
+    // 秘訣・集中加工・集中作業が使える状態かどうか
+    pub fn is_good_or_excellent(&self) -> bool {
+        *self == Condition::HighQuality || *self == Condition::Excellent
+    }
+}
+
 impl Modifier {
     fn try_random(&mut self, success_rate : f32) -> bool {
-        self.rng.next_f32() < success_rate
+        match self.forced_success {
+            Some(x) => x,
+            None => self.rng.next_f32() < success_rate,
+        }
     }
 }
 
@@ -207,7 +329,7 @@ impl State {
             durability:mod_param.max_durability,
             cp:mod_param.max_cp,
             inner_quiet:0,
-            careful_observation:3,
+            careful_observation:mod_param.capability.careful_observation(),
             waste_not:0,
             veneration:0,
             great_strides:0,
@@ -217,6 +339,10 @@ impl State {
             manipulation:0,
             heart_and_soul:false,
             heart_and_soul_used:false,
+            trained_perfection:false,
+            trained_perfection_used:false,
+            quick_innovation_used:false,
+            expedience:false,
             combo_basic_touch:false,
             combo_standard_touch:false,
             combo_observe:false,
@@ -238,41 +364,8 @@ impl State {
 
     // 必要CP一覧
     // CPはStateに依存した関数であるためStateの関数とします
-    pub fn get_required_cp(&self, a:&Action) -> u32 {
-        let required_cp = match a {
-            Action::BasicSynthesis => 0,
-            Action::BasicTouch => 18,
-            Action::MastersMend => 88,
-            Action::HastyTouch => 0,
-            Action::RapidSynthesis => 0,
-            Action::Observe => 7,
-            Action::TricksOfTheTrade => 0,
-            Action::WasteNot => 56,
-            Action::Veneration => 18,
-            Action::StandardTouch => if self.combo_basic_touch { 18 } else { 32 },
-            Action::GreatStrides => 32,
-            Action::Innovation => 18,
-            Action::FinalAppraisal => 1,
-            Action::WasteNot2 => 98,
-            Action::ByregotsBlessing => 24,
-            Action::PreciseTouch => 18,
-            Action::MuscleMemory => 6,
-            Action::CarefulObservation => 0,
-            Action::CarefulSynthesis => 7,
-            Action::Manipulation => 96,
-            Action::PrudentTouch => 25,
-            Action::FocusedSynthesis => 5,
-            Action::FocusedTouch => 18,
-            Action::Reflect => 6,
-            Action::PreparatoryTouch => 40,
-            Action::Groundwork => 18,
-            Action::DelicateSynthesis => 32,
-            Action::IntensiveSynthesis => 6,
-            Action::AdvancedTouch => if self.combo_standard_touch { 18 } else { 46 },
-            Action::HeartAndSoul => 0,
-            Action::PrudentSynthesis => 18,
-            Action::TrainedFinesse => 32,
-        };
+    pub fn get_required_cp(&self, mod_param:&ModifierParameter, a:&Action) -> u32 {
+        let required_cp = mod_param.ruleset.base_cp(self, a);
 
         // 高能率の場合は半減しますが端数切り上げなので1足します
         if self.condition == Condition::HighEfficiency {
@@ -283,26 +376,13 @@ impl State {
         }
     }
 
-    // Velvet Weissmelさんの統計データを参考に設定しています
-    // https://jp.finalfantasyxiv.com/lodestone/character/3514261/blog/4645845/
-    fn next_condition(rng:&mut Xorshift128) -> Condition {
-        let v = rng.next_f32();
-        match v {
-            _ if v < 0.37 => Condition::Standard,
-            _ if v < 0.49 => Condition::HighQuality,
-            _ if v < 0.61 => Condition::HighProgress,
-            _ if v < 0.73 => Condition::Stable,
-            _ if v < 0.85 => Condition::HighSustain,
-            _             => Condition::Solid,
-        }
-    }
-
+    // 状態の遷移はレシピごとのモデルに従います
     fn change_condition(&self,modifier:&mut Modifier) -> State {
         if self.is_terminated() {
             self.clone()
         }
         else {
-            State { condition: State::next_condition(&mut modifier.rng), .. *self }
+            State { condition: modifier.mod_param.condition_model.sample(self, &mut modifier.rng), .. *self }
         }
     }
 
@@ -320,19 +400,43 @@ impl State {
             combo_basic_touch: false,
             combo_standard_touch: false,
             combo_observe: false,
+            expedience: false,
             .. *self
         }
     }
 
-    fn duration(&self,x:u32) -> u32 {
+    fn duration(&self, mod_param:&ModifierParameter, a:&Action) -> u32 {
+        let x = mod_param.ruleset.buff_duration(a);
         if self.condition == Condition::HighSustain { x+2 } else { x }
     }
 
-    fn probability(&self,x:f32) -> f32 {
+    fn probability(&self, mod_param:&ModifierParameter, a:&Action) -> f32 {
+        let x = mod_param.ruleset.success_rate(a);
         if self.condition == Condition::Stable { x+0.25 } else { x }
     }
 
-    fn add_working(&self, mod_param:&ModifierParameter, efficiency : u32) -> State {
+    // 倹約・頑丈を考慮した消費耐久です
+    fn get_durability_cost(&self, mod_param:&ModifierParameter, a:&Action) -> u32 {
+        let x = mod_param.ruleset.durability_cost(a);
+        let cond_rate = if self.condition == Condition::Solid || self.condition == Condition::Robust { 0.5 } else { 1.0 };
+        let waste_not_rate = if self.waste_not > 0 { 0.5 } else { 1.0 };
+        (((x as f32) * cond_rate * waste_not_rate).ceil()) as u32
+    }
+
+    fn get_progress_efficiency(&self, mod_param:&ModifierParameter, a:&Action) -> u32 {
+        let efficiency = mod_param.capability.progress_efficiency(a, mod_param.ruleset.progress_efficiency(self, a));
+
+        // 下地作業は耐久が足りない場合に効率が半減します
+        if *a == Action::Groundwork && self.durability < self.get_durability_cost(mod_param, a) {
+            efficiency / 2
+        }
+        else {
+            efficiency
+        }
+    }
+
+    fn add_working(&self, mod_param:&ModifierParameter, a:&Action) -> State {
+        let efficiency = self.get_progress_efficiency(mod_param, a);
         let w = self.working + mod_param.advance_table.working_advance(efficiency, self.condition == Condition::HighProgress, self.veneration > 0, self.muscle_memory > 0);
 
         if w >= mod_param.max_working {
@@ -350,15 +454,15 @@ impl State {
 
     fn add_quality_base(&self, mod_param:&ModifierParameter, efficiency:u32) -> State {
         State {
-            quality: min(self.quality + mod_param.advance_table.quality_advance(efficiency, self.condition == Condition::HighQuality, self.innovation > 0, self.great_strides > 0, self.inner_quiet), mod_param.max_quality),
+            quality: min(self.quality + mod_param.advance_table.quality_advance(efficiency, self.condition.quality_rate(), self.innovation > 0, self.great_strides > 0, self.inner_quiet), mod_param.max_quality),
             great_strides: 0,
             .. *self
         }
     }
 
-    fn add_inner_quiet(&self, inner_quiet_stack:u32) -> State {
+    fn add_inner_quiet(&self, mod_param:&ModifierParameter, inner_quiet_stack:u32) -> State {
         State {
-            inner_quiet: min(self.inner_quiet+inner_quiet_stack,10),
+            inner_quiet: min(self.inner_quiet+mod_param.capability.inner_quiet_stack(inner_quiet_stack),10),
             .. *self
         }
     }
@@ -371,28 +475,34 @@ impl State {
         State { inner_quiet: inner_quiet, .. *self }
     }
 
-    fn add_quality(&self, mod_param:&ModifierParameter, efficiency:u32, inner_quiet_stack:u32) -> State {
-        self.add_quality_base(&mod_param,efficiency).add_inner_quiet(inner_quiet_stack)
+    fn add_quality(&self, mod_param:&ModifierParameter, a:&Action) -> State {
+        let efficiency = mod_param.ruleset.quality_efficiency(self, a);
+        self.add_quality_base(&mod_param,efficiency).add_inner_quiet(mod_param,mod_param.ruleset.inner_quiet_stack(self, a))
     }
 
     fn add_quality_byregots(&self, mod_param:&ModifierParameter) -> State {
-        self.add_quality_base(&mod_param,100 + self.inner_quiet * 20).set_inner_quiet(0)
+        let efficiency = mod_param.ruleset.quality_efficiency(self, &Action::ByregotsBlessing);
+        self.add_quality_base(&mod_param,efficiency).set_inner_quiet(0)
     }
 
     fn consume_careful_observation(&self) -> State {
         State { careful_observation: self.careful_observation-1, .. *self }
     }
 
-    fn consume_cp(&self, a:&Action) -> State {
-        State { cp:self.cp - self.get_required_cp(a), .. *self }
+    fn consume_cp(&self, mod_param:&ModifierParameter, a:&Action) -> State {
+        State { cp:self.cp - self.get_required_cp(mod_param, a), .. *self }
     }
 
-    fn consume_durability(&self, x:u32) -> State {
-        let cond_rate = if self.condition == Condition::Solid { 0.5 } else { 1.0 };
-        let waste_not_rate = if self.waste_not > 0 { 0.5 } else { 1.0 };
-        let q = (((x as f32) * cond_rate * waste_not_rate).ceil()) as u32;
+    fn consume_durability(&self, mod_param:&ModifierParameter, a:&Action) -> State {
+        let q = self.get_durability_cost(mod_param, a);
 
-        State { durability: if self.durability > q { self.durability - q } else { 0 }, .. *self }
+        if self.trained_perfection && q > 0 {
+            // 匠の絶技が有効なら耐久を消費せずに効果を消します
+            State { trained_perfection: false, .. *self }
+        }
+        else {
+            State { durability: if self.durability > q { self.durability - q } else { 0 }, .. *self }
+        }
     }
 
     fn add_durability(&self, x:u32, mod_param:&ModifierParameter) -> State {
@@ -407,38 +517,50 @@ impl State {
         State { manipulation:0, .. *self }
     }
 
-    fn set_manipulation(&self, dur:u32) -> State {
-        State { manipulation: self.duration(dur), .. *self }
+    fn set_manipulation(&self, mod_param:&ModifierParameter) -> State {
+        State { manipulation: self.duration(mod_param, &Action::Manipulation), .. *self }
     }
 
-    fn set_great_strides(&self, dur:u32) -> State {
-        State { great_strides: self.duration(dur), .. *self }
+    fn set_great_strides(&self, mod_param:&ModifierParameter) -> State {
+        State { great_strides: self.duration(mod_param, &Action::GreatStrides), .. *self }
     }
 
-    fn set_innovation(&self, dur:u32) -> State {
-        State { innovation: self.duration(dur), .. *self }
+    fn set_innovation(&self, mod_param:&ModifierParameter) -> State {
+        State { innovation: self.duration(mod_param, &Action::Innovation), .. *self }
     }
 
-    fn set_waste_not(&self, dur:u32) -> State {
-        State { waste_not: self.duration(dur), .. *self }
+    fn set_waste_not(&self, mod_param:&ModifierParameter, a:&Action) -> State {
+        State { waste_not: self.duration(mod_param, a), .. *self }
     }
 
-    fn set_veneration(&self, dur:u32) -> State {
-        State { veneration: self.duration(dur), .. *self }
+    fn set_veneration(&self, mod_param:&ModifierParameter) -> State {
+        State { veneration: self.duration(mod_param, &Action::Veneration), .. *self }
     }
 
-    fn set_final_appraisal(&self, dur:u32) -> State {
-        State { final_appraisal: self.duration(dur), .. *self }
+    fn set_final_appraisal(&self, mod_param:&ModifierParameter) -> State {
+        State { final_appraisal: self.duration(mod_param, &Action::FinalAppraisal), .. *self }
     }
 
-    fn set_muscle_memory(&self, dur:u32) -> State {
-        State { muscle_memory: self.duration(dur), .. *self }
+    fn set_muscle_memory(&self, mod_param:&ModifierParameter) -> State {
+        State { muscle_memory: self.duration(mod_param, &Action::MuscleMemory), .. *self }
     }
 
     fn set_heart_and_soul(&self) -> State {
         State { heart_and_soul: true, heart_and_soul_used: true, .. *self }
     }
 
+    fn set_trained_perfection(&self) -> State {
+        State { trained_perfection: true, trained_perfection_used: true, .. *self }
+    }
+
+    fn set_quick_innovation(&self, mod_param:&ModifierParameter) -> State {
+        State { innovation: self.duration(mod_param, &Action::QuickInnovation), quick_innovation_used: true, .. *self }
+    }
+
+    fn set_expedience(&self) -> State {
+        State { expedience: true, .. *self }
+    }
+
     fn set_combo_basic_touch(&self) -> State {
         State { combo_basic_touch: true, .. *self }
     }
@@ -456,7 +578,7 @@ impl State {
     }
 
     fn clear_heart_and_soul(&self) -> State {
-        if self.condition == Condition::HighQuality {
+        if self.condition.is_good_or_excellent() {
             self.clone()
         }
         else {
@@ -465,223 +587,423 @@ impl State {
     }
 
     // 実行確認
-    pub fn check_action(&self, a:&Action) -> bool {
-        if self.cp >= self.get_required_cp(&a) {
-            match a {
-                Action::TricksOfTheTrade => self.condition == Condition::HighQuality || self.heart_and_soul,
-                Action::ByregotsBlessing => self.inner_quiet > 0, // ビエルゴはinner_quiet初期値の時は使えません
-                Action::PreciseTouch => self.condition == Condition::HighQuality || self.heart_and_soul,
-                Action::MuscleMemory => self.turn == 1, // 確信バフは最終確認で消えません
-                Action::CarefulObservation => self.careful_observation > 0,
-                Action::PrudentTouch => self.waste_not == 0,
-                Action::Reflect => self.turn == 1, // 真価バフは最終確認で消えません
-                Action::IntensiveSynthesis => self.condition == Condition::HighQuality || self.heart_and_soul,
-                Action::HeartAndSoul => !self.heart_and_soul_used,
-                Action::PrudentSynthesis => self.waste_not == 0,
-                Action::TrainedFinesse => self.inner_quiet == 10, // 匠の神業はIQ10限定
-                _ => true
-            }
+    pub fn check_action(&self, mod_param:&ModifierParameter, a:&Action) -> bool {
+        self.check_action_reason(mod_param, a).is_ok()
+    }
+
+    // 実行確認を行い、実行できない場合はその理由を返します
+    pub fn check_action_reason(&self, mod_param:&ModifierParameter, a:&Action) -> Result<(),IllegalReason> {
+        fn require(cond:bool, reason:IllegalReason) -> Result<(),IllegalReason> {
+            if cond { Ok(()) } else { Err(reason) }
         }
-        else {
-            false
+
+        require(mod_param.ruleset.is_available(a), IllegalReason::Unavailable)?;
+        require(mod_param.capability.is_learned(a), IllegalReason::NotLearned)?;
+
+        let required_cp = self.get_required_cp(mod_param, a);
+        require(self.cp >= required_cp, IllegalReason::InsufficientCp(required_cp))?;
+
+        let good = self.condition.is_good_or_excellent() || self.heart_and_soul;
+        match a {
+            Action::TricksOfTheTrade => require(good, IllegalReason::WrongCondition),
+            Action::ByregotsBlessing => require(self.inner_quiet > 0, IllegalReason::InnerQuietEmpty), // ビエルゴはinner_quiet初期値の時は使えません
+            Action::PreciseTouch => require(good, IllegalReason::WrongCondition),
+            Action::MuscleMemory => require(self.turn == 1, IllegalReason::TurnRestriction), // 確信バフは最終確認で消えません
+            Action::CarefulObservation => require(self.careful_observation > 0, IllegalReason::NoCarefulObservation),
+            Action::PrudentTouch => require(self.waste_not == 0, IllegalReason::WasteNotActive),
+            Action::Reflect => require(self.turn == 1, IllegalReason::TurnRestriction), // 真価バフは最終確認で消えません
+            Action::IntensiveSynthesis => require(good, IllegalReason::WrongCondition),
+            Action::HeartAndSoul => require(!self.heart_and_soul_used, IllegalReason::HeartAndSoulUsed),
+            Action::PrudentSynthesis => require(self.waste_not == 0, IllegalReason::WasteNotActive),
+            Action::TrainedFinesse => require(self.inner_quiet == 10, IllegalReason::InnerQuietNotMax), // 匠の神業はIQ10限定
+            Action::TrainedPerfection => require(!self.trained_perfection_used, IllegalReason::AlreadyUsed),
+            Action::DaringTouch => require(self.expedience, IllegalReason::NoExpedience), // ヘイスティタッチ成功直後のみ
+            Action::QuickInnovation => {
+                require(!self.quick_innovation_used, IllegalReason::AlreadyUsed)?;
+                require(self.innovation == 0, IllegalReason::InnovationActive)
+            },
+            _ => Ok(())
         }
     }
 
     // 作業
     fn action_basic_synthesis(&self, modifier:&mut Modifier) -> State {
-        self.add_working(&modifier.mod_param,120).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+        let a = Action::BasicSynthesis;
+        self.add_working(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
     }
 
     // 加工
     fn action_basic_touch(&self, modifier:&mut Modifier) -> State {
-        self.add_quality(&modifier.mod_param,100,1).consume_cp(&Action::BasicTouch).consume_durability(10).next_turn(modifier).set_combo_basic_touch().change_condition(modifier).add_time(3)
+        let a = Action::BasicTouch;
+        self.add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).set_combo_basic_touch().change_condition(modifier)
     }
 
     // マスターズメンド
     fn action_masters_mend(&self, modifier:&mut Modifier) -> State {
-        self.consume_cp(&Action::MastersMend).add_durability(30,&modifier.mod_param).next_turn(modifier).change_condition(modifier).add_time(2)
+        self.consume_cp(&modifier.mod_param,&Action::MastersMend).add_durability(30,&modifier.mod_param).next_turn(modifier).change_condition(modifier)
     }
 
     // ヘイスティタッチ
     fn action_hasty_touch(&self, modifier:&mut Modifier) -> State {
-        if modifier.try_random(self.probability(0.5)) {
+        let a = Action::HastyTouch;
+        if modifier.try_random(self.probability(&modifier.mod_param,&a)) {
             // 成功時
-            self.add_quality(&modifier.mod_param,100,1).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+            self.add_quality(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).set_expedience().change_condition(modifier)
         }
         else {
             // 失敗時
-            self.consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+            self.consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
         }
     }
 
     // 突貫作業
     fn action_rapid_synthesis(&self, modifier:&mut Modifier) -> State {
-        if modifier.try_random(self.probability(0.5)) {
+        let a = Action::RapidSynthesis;
+        if modifier.try_random(self.probability(&modifier.mod_param,&a)) {
             // 成功時
-            self.add_working(&modifier.mod_param,500).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+            self.add_working(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
         }
         else {
             // 失敗時
-            self.consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+            self.consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
         }
     }
 
     // 経過観察
     fn action_observe(&self, modifier:&mut Modifier) -> State {
-        self.consume_cp(&Action::Observe).next_turn(modifier).set_combo_observe().change_condition(modifier).add_time(3)
+        self.consume_cp(&modifier.mod_param,&Action::Observe).next_turn(modifier).set_combo_observe().change_condition(modifier)
     }
 
     // 秘訣
     fn action_trick_of_the_trade(&self, modifier:&mut Modifier) -> State {
-        self.next_turn(modifier).add_cp(20,&modifier.mod_param).clear_heart_and_soul().change_condition(modifier).add_time(3)
+        self.next_turn(modifier).add_cp(20,&modifier.mod_param).clear_heart_and_soul().change_condition(modifier)
     }
 
     // 倹約
     fn action_waste_not(&self, modifier:&mut Modifier) -> State {
-        self.consume_cp(&Action::WasteNot).next_turn(modifier).set_waste_not(4).change_condition(modifier).add_time(2)
+        let a = Action::WasteNot;
+        self.consume_cp(&modifier.mod_param,&a).next_turn(modifier).set_waste_not(&modifier.mod_param,&a).change_condition(modifier)
     }
 
     // ヴェネレーション
     fn action_veneration(&self, modifier:&mut Modifier) -> State {
-        self.consume_cp(&Action::Veneration).next_turn(modifier).set_veneration(4).change_condition(modifier).add_time(2)
+        self.consume_cp(&modifier.mod_param,&Action::Veneration).next_turn(modifier).set_veneration(&modifier.mod_param).change_condition(modifier)
     }
 
     // 中級加工
     fn action_standard_touch(&self, modifier:&mut Modifier) -> State {
         // 上級加工へのコンボは直前の中級加工コンボが有効でなければ発動しません
+        let a = Action::StandardTouch;
         let combo_basic_touch = self.combo_basic_touch;
-        self.add_quality(&modifier.mod_param,125,1).consume_cp(&Action::StandardTouch).consume_durability(10).next_turn(modifier).set_combo_standard_touch(combo_basic_touch).change_condition(modifier).add_time(3)
+        self.add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).set_combo_standard_touch(combo_basic_touch).change_condition(modifier)
     }
 
     // グレートストライド
     fn action_great_strides(&self, modifier:&mut Modifier) -> State {
-        self.consume_cp(&Action::GreatStrides).next_turn(modifier).set_great_strides(3).change_condition(modifier).add_time(2)
+        self.consume_cp(&modifier.mod_param,&Action::GreatStrides).next_turn(modifier).set_great_strides(&modifier.mod_param).change_condition(modifier)
     }
 
     // イノベーション
     fn action_innovation(&self, modifier:&mut Modifier) -> State {
-        self.consume_cp(&Action::Innovation).next_turn(modifier).set_innovation(4).change_condition(modifier).add_time(2)
+        self.consume_cp(&modifier.mod_param,&Action::Innovation).next_turn(modifier).set_innovation(&modifier.mod_param).change_condition(modifier)
     }
 
     // 最終確認
-    fn action_final_apprisal(&self, _modifier:&mut Modifier) -> State {
-        self.consume_cp(&Action::FinalAppraisal).clear_combo().set_final_appraisal(5).add_time(2)
+    fn action_final_apprisal(&self, modifier:&mut Modifier) -> State {
+        self.consume_cp(&modifier.mod_param,&Action::FinalAppraisal).clear_combo().set_final_appraisal(&modifier.mod_param)
     }
 
     // 長期倹約
     fn action_waste_not2(&self, modifier:&mut Modifier) -> State {
-        self.consume_cp(&Action::WasteNot2).next_turn(modifier).set_waste_not(8).change_condition(modifier).add_time(2)
+        let a = Action::WasteNot2;
+        self.consume_cp(&modifier.mod_param,&a).next_turn(modifier).set_waste_not(&modifier.mod_param,&a).change_condition(modifier)
     }
 
     // ビエルゴの祝福
     fn action_byregots_blessing(&self, modifier:&mut Modifier) -> State {
-        self.add_quality_byregots(&modifier.mod_param).consume_cp(&Action::ByregotsBlessing).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+        let a = Action::ByregotsBlessing;
+        self.add_quality_byregots(&modifier.mod_param).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
     }
 
     // 集中加工
     fn action_precise_touch(&self, modifier:&mut Modifier) -> State {
-        self.add_quality(&modifier.mod_param,150,2).consume_cp(&Action::PreciseTouch).consume_durability(10).next_turn(modifier).clear_heart_and_soul().change_condition(modifier).add_time(3)
+        let a = Action::PreciseTouch;
+        self.add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).clear_heart_and_soul().change_condition(modifier)
     }
 
     // 確信
     fn action_muscle_memory(&self, modifier:&mut Modifier) -> State {
-        self.add_working(&modifier.mod_param,300).consume_cp(&Action::MuscleMemory).consume_durability(10).next_turn(modifier).set_muscle_memory(5).change_condition(modifier).add_time(3)
+        let a = Action::MuscleMemory;
+        self.add_working(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).set_muscle_memory(&modifier.mod_param).change_condition(modifier)
     }
 
     // 設計変更
     fn action_careful_observation(&self, modifier:&mut Modifier) -> State {
-        self.clear_combo().consume_careful_observation().change_condition(modifier).add_time(2)
+        self.clear_combo().consume_careful_observation().change_condition(modifier)
     }
 
     // 模範作業
     fn action_careful_synthesis(&self, modifier:&mut Modifier) -> State {
-        self.add_working(&modifier.mod_param,180).consume_cp(&Action::CarefulSynthesis).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+        let a = Action::CarefulSynthesis;
+        self.add_working(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
     }
 
     // マニピュレーション
     fn action_manipulation(&self, modifier:&mut Modifier) -> State {
-        self.clear_manipulation().consume_cp(&Action::Manipulation).next_turn(modifier).set_manipulation(8).change_condition(modifier).add_time(2)
+        self.clear_manipulation().consume_cp(&modifier.mod_param,&Action::Manipulation).next_turn(modifier).set_manipulation(&modifier.mod_param).change_condition(modifier)
     }
 
     // 倹約加工
     fn action_prudent_touch(&self, modifier:&mut Modifier) -> State {
-        self.add_quality(&modifier.mod_param,100,1).consume_cp(&Action::PrudentTouch).consume_durability(5).next_turn(modifier).change_condition(modifier).add_time(3)
+        let a = Action::PrudentTouch;
+        self.add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
     }
 
     // 注視作業
     fn action_focused_synthesis(&self, modifier:&mut Modifier) -> State {
-        if self.combo_observe || modifier.try_random(self.probability(0.5)) {
+        let a = Action::FocusedSynthesis;
+        if self.combo_observe || modifier.try_random(self.probability(&modifier.mod_param,&a)) {
             // 成功の場合
-            self.add_working(&modifier.mod_param,150).consume_cp(&Action::FocusedSynthesis).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+            self.add_working(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
         }
         else {
             // 失敗の場合
-            self.consume_cp(&Action::FocusedSynthesis).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+            self.consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
         }
     }
 
     // 注視作業
     fn action_focused_touch(&self, modifier:&mut Modifier) -> State {
-        if self.combo_observe || modifier.try_random(self.probability(0.5)) {
+        let a = Action::FocusedTouch;
+        if self.combo_observe || modifier.try_random(self.probability(&modifier.mod_param,&a)) {
             // 成功の場合
-            self.add_quality(&modifier.mod_param,150,1).consume_cp(&Action::FocusedTouch).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+            self.add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
         }
         else {
             // 失敗の場合
-            self.consume_cp(&Action::FocusedTouch).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+            self.consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
         }
     }
 
     // 真価
     fn action_reflect(&self, modifier:&mut Modifier) -> State {
-        self.add_quality(&modifier.mod_param,100,2).consume_cp(&Action::Reflect).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+        let a = Action::Reflect;
+        self.add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
     }
 
     // 下地加工
     fn action_preparatory_touch(&self, modifier:&mut Modifier) -> State {
-        self.add_quality(&modifier.mod_param,200,2).consume_cp(&Action::PreparatoryTouch).consume_durability(20).next_turn(modifier).change_condition(modifier).add_time(3)
+        let a = Action::PreparatoryTouch;
+        self.add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
     }
 
     // 下地作業
     fn action_groundwork(&self, modifier:&mut Modifier) -> State {
-        let efficiency = if self.get_required_cp(&Action::Groundwork) < self.durability as u32 { 180 } else { 360 };
-
-        self.add_working(&modifier.mod_param,efficiency).consume_cp(&Action::Groundwork).consume_durability(20).next_turn(modifier).change_condition(modifier).add_time(3)
+        let a = Action::Groundwork;
+        self.add_working(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
     }
 
     // 精密作業
     fn action_delecate_synthesis(&self, modifier:&mut Modifier) -> State {
-        self.add_working(&modifier.mod_param,100).add_quality(&modifier.mod_param,100,1).consume_cp(&Action::DelicateSynthesis).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+        let a = Action::DelicateSynthesis;
+        self.add_working(&modifier.mod_param,&a).add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
     }
 
     // 集中作業
     fn action_intensive_synthesis(&self, modifier:&mut Modifier) -> State {
-        self.add_working(&modifier.mod_param,400).consume_cp(&Action::IntensiveSynthesis).consume_durability(10).next_turn(modifier).clear_heart_and_soul().change_condition(modifier).add_time(3)
+        let a = Action::IntensiveSynthesis;
+        self.add_working(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).clear_heart_and_soul().change_condition(modifier)
     }
 
     // 上級加工
     fn action_advanced_touch(&self, modifier:&mut Modifier) -> State {
-        self.add_quality(&modifier.mod_param,150,1).consume_cp(&Action::AdvancedTouch).consume_durability(10).next_turn(modifier).change_condition(modifier).add_time(3)
+        let a = Action::AdvancedTouch;
+        self.add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
     }
 
     // 一心不乱
     fn action_heart_and_soul(&self, _modifier:&mut Modifier) -> State {
-        self.clear_combo().set_heart_and_soul().add_time(2)
+        self.clear_combo().set_heart_and_soul()
     }
 
     // 倹約作業
     fn action_prudent_synthesis(&self, modifier:&mut Modifier) -> State {
-        self.add_working(&modifier.mod_param,180).consume_cp(&Action::PrudentSynthesis).consume_durability(5).next_turn(modifier).change_condition(modifier).add_time(3)
+        let a = Action::PrudentSynthesis;
+        self.add_working(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
     }
 
     // 匠の神業
     fn action_trained_finesse(&self, modifier:&mut Modifier) -> State {
-        self.add_quality(&modifier.mod_param,100,1).consume_cp(&Action::TrainedFinesse).next_turn(modifier).change_condition(modifier).add_time(3)
+        let a = Action::TrainedFinesse;
+        self.add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
+    }
+
+    // パーフェクトメンド
+    fn action_immaculate_mend(&self, modifier:&mut Modifier) -> State {
+        self.consume_cp(&modifier.mod_param,&Action::ImmaculateMend).add_durability(modifier.mod_param.max_durability,&modifier.mod_param).next_turn(modifier).change_condition(modifier)
+    }
+
+    // 匠の絶技
+    fn action_trained_perfection(&self, modifier:&mut Modifier) -> State {
+        self.consume_cp(&modifier.mod_param,&Action::TrainedPerfection).next_turn(modifier).set_trained_perfection().change_condition(modifier)
+    }
+
+    // デアリングタッチ
+    fn action_daring_touch(&self, modifier:&mut Modifier) -> State {
+        let a = Action::DaringTouch;
+        if modifier.try_random(self.probability(&modifier.mod_param,&a)) {
+            // 成功時
+            self.add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
+        }
+        else {
+            // 失敗時
+            self.consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
+        }
+    }
+
+    // 洗練加工
+    fn action_refined_touch(&self, modifier:&mut Modifier) -> State {
+        let a = Action::RefinedTouch;
+        self.add_quality(&modifier.mod_param,&a).consume_cp(&modifier.mod_param,&a).consume_durability(&modifier.mod_param,&a).next_turn(modifier).change_condition(modifier)
+    }
+
+    // クイックイノベーション
+    fn action_quick_innovation(&self, modifier:&mut Modifier) -> State {
+        self.consume_cp(&modifier.mod_param,&Action::QuickInnovation).clear_combo().set_quick_innovation(&modifier.mod_param)
+    }
+
+    // 成否が乱数で決まる場合の成功率です。必ず成功する場合はNoneを返します
+    fn random_success_rate(&self, mod_param:&ModifierParameter, a:&Action) -> Option<f32> {
+        match a {
+            Action::HastyTouch | Action::RapidSynthesis | Action::DaringTouch => Some(self.probability(mod_param, a).min(1.0)),
+            Action::FocusedSynthesis | Action::FocusedTouch if !self.combo_observe => Some(self.probability(mod_param, a).min(1.0)),
+            _ => None,
+        }
+    }
+
+    // 実行後に状態が変化するアクションかどうか
+    fn changes_condition(a:&Action) -> bool {
+        match a {
+            Action::FinalAppraisal | Action::HeartAndSoul | Action::QuickInnovation => false,
+            _ => true,
+        }
+    }
+
+    // アクション実行後に起こりうる全ての状態と、その確率を返します。
+    // 成否と次の状態の組み合わせを列挙します。確率の総和は1.0になります
+    pub fn outcomes(&self, modifier:&mut Modifier, a:&Action) -> Vec<(State,f32)> {
+        let procs = match self.random_success_rate(&modifier.mod_param, a) {
+            Some(p) => vec![(true,p),(false,1.0-p)],
+            None => vec![(true,1.0)],
+        };
+
+        let mut ret = vec![];
+        for (success,p) in procs.into_iter().filter(|(_,p)| *p > 0.0) {
+            modifier.forced_success = Some(success);
+            let ns = self.run_action(modifier, a);
+            modifier.forced_success = None;
+
+            if ns.is_terminated() || !State::changes_condition(a) {
+                ret.push((ns,p));
+            }
+            else {
+                // 遷移前の状態を戻して、次の状態の分布を求めます
+                let before = State { condition:self.condition, .. ns.clone() };
+                for (c,q) in modifier.mod_param.condition_model.distribution(&before) {
+                    if q > 0.0 {
+                        ret.push((State { condition:c, .. ns.clone() }, p*q));
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    // アクションを実行し、起きた出来事も合わせて返します。
+    // 乱数の消費順は run_action と同じです
+    pub fn run_action_with_events(&self, modifier:&mut Modifier, a:&Action) -> (State,Vec<Event>) {
+        let mut events = vec![];
+
+        // 成否を先に決めておきます
+        let success = match self.random_success_rate(&modifier.mod_param, a) {
+            Some(p) => {
+                let success = modifier.try_random(p);
+                events.push(Event::Proc { success, probability:p });
+                success
+            },
+            None => true,
+        };
+
+        let forced_success = modifier.forced_success;
+        modifier.forced_success = Some(success);
+        let ns = self.run_action(modifier, a);
+        modifier.forced_success = forced_success;
+
+        let mod_param = &modifier.mod_param;
+
+        if success && mod_param.ruleset.progress_efficiency(self, a) > 0 {
+            events.push(Event::Progress { efficiency:self.get_progress_efficiency(mod_param, a), amount:ns.working - self.working });
+        }
+
+        if success && mod_param.ruleset.quality_efficiency(self, a) > 0 {
+            events.push(Event::Quality {
+                efficiency: mod_param.ruleset.quality_efficiency(self, a),
+                condition_rate: self.condition.quality_rate(),
+                inner_quiet: self.inner_quiet,
+                innovation: self.innovation > 0,
+                great_strides: self.great_strides > 0,
+                amount: ns.quality - self.quality,
+            });
+        }
+
+        if self.final_appraisal > 0 && ns.final_appraisal == 0 && ns.working == mod_param.max_working - 1 && self.working < ns.working {
+            events.push(Event::FinalAppraisalTriggered);
+        }
+
+        let base = mod_param.ruleset.durability_cost(a);
+        let cost = self.get_durability_cost(mod_param, a);
+        let lost = if base == 0 || self.trained_perfection { 0 } else { min(cost, self.durability) };
+        if base > 0 {
+            events.push(Event::Durability { base, cost, lost });
+        }
+
+        // ターン経過前の耐久から回復量を求めます。マニピュレーション自身は上書きされるため回復しません
+        if ns.turn > self.turn && self.manipulation > 0 && *a != Action::Manipulation {
+            let durability = match a {
+                Action::MastersMend => min(self.durability + 30, mod_param.max_durability),
+                Action::ImmaculateMend => mod_param.max_durability,
+                _ => self.durability - lost,
+            };
+            events.push(Event::ManipulationRegen(ns.durability - durability));
+        }
+
+        let buffs = [
+            (Buff::WasteNot, self.waste_not > 0, ns.waste_not > 0),
+            (Buff::Veneration, self.veneration > 0, ns.veneration > 0),
+            (Buff::GreatStrides, self.great_strides > 0, ns.great_strides > 0),
+            (Buff::Innovation, self.innovation > 0, ns.innovation > 0),
+            (Buff::FinalAppraisal, self.final_appraisal > 0, ns.final_appraisal > 0),
+            (Buff::MuscleMemory, self.muscle_memory > 0, ns.muscle_memory > 0),
+            (Buff::Manipulation, self.manipulation > 0, ns.manipulation > 0),
+            (Buff::HeartAndSoul, self.heart_and_soul, ns.heart_and_soul),
+            (Buff::TrainedPerfection, self.trained_perfection, ns.trained_perfection),
+        ];
+        for (buff,before,after) in buffs.iter() {
+            if *before && !*after {
+                events.push(Event::BuffExpired(*buff));
+            }
+        }
+
+        if !ns.is_terminated() && State::changes_condition(a) {
+            events.push(Event::ConditionRolled(ns.condition));
+        }
+
+        (ns,events)
     }
 
     // アクション取得
     pub fn run_action(&self, modifier:&mut Modifier, a:&Action) -> State {
-        match a {
+        let s = match a {
             Action::BasicSynthesis => self.action_basic_synthesis(modifier),
             Action::BasicTouch => self.action_basic_touch(modifier),
             Action::MastersMend => self.action_masters_mend(modifier),
@@ -714,24 +1036,86 @@ impl State {
             Action::HeartAndSoul => self.action_heart_and_soul(modifier),
             Action::PrudentSynthesis => self.action_prudent_synthesis(modifier),
             Action::TrainedFinesse => self.action_trained_finesse(modifier),
-        }
+            Action::ImmaculateMend => self.action_immaculate_mend(modifier),
+            Action::TrainedPerfection => self.action_trained_perfection(modifier),
+            Action::DaringTouch => self.action_daring_touch(modifier),
+            Action::RefinedTouch => self.action_refined_touch(modifier),
+            Action::QuickInnovation => self.action_quick_innovation(modifier),
+        };
+        s.add_time(modifier.mod_param.timing.duration_ms(a))
     }
 }
 
-// Velvet Weissmelさんの調査式を元に計算
-// https://jp.finalfantasyxiv.com/lodestone/character/3514261/blog/4645845/
-pub fn get_technical_point(worth:u32) -> u32 {
-    if worth < 5800 {
-        0
-    }
-    else if worth < 6500 {
-        (1*worth - 4050) / 10
-    }
-    else if worth < 7400 {
-        (3*worth - 15800) / 10
-    }
-    else {
-        (6*worth - 36200) / 10
-    }
+#[test]
+fn test_outcomes()
+{
+    use xorshift::SeedableRng;
+
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let mut modifier = Modifier { mod_param:mod_param.clone(), rng:SeedableRng::from_seed(&[1u64,1u64][..]), forced_success:None };
+    let s = State::new(&mod_param);
+
+    // 成否2通り x 状態6通り
+    let xs = s.outcomes(&mut modifier, &Action::HastyTouch);
+    assert_eq!( 12, xs.len() );
+    assert!( (xs.iter().map(|(_,p)| p).sum::<f32>() - 1.0).abs() < 1e-5 );
+    assert!( xs.iter().any(|(x,_)| x.quality == 0) );
+    assert!( xs.iter().any(|(x,_)| x.quality > 0) );
+
+    // 最終確認は状態が変化しません
+    assert_eq!( 1, s.outcomes(&mut modifier, &Action::FinalAppraisal).len() );
+
+    // 耐久が尽きる手は状態の遷移を展開しません。安定では成功率が25%上がります
+    let s = State { turn:2, durability:10, condition:Condition::Stable, .. s };
+    let xs = s.outcomes(&mut modifier, &Action::HastyTouch);
+    assert_eq!( 2, xs.len() );
+    assert!( xs[0].0.quality > 0 && xs[1].0.quality == 0 );
+    let p = mod_param.ruleset.success_rate(&Action::HastyTouch) + 0.25;
+    assert!( (xs[0].1 - p).abs() < 1e-5 && (xs[1].1 - (1.0 - p)).abs() < 1e-5 );
+    assert_eq!( None, modifier.forced_success );
+}
+
+#[test]
+fn test_check_action_reason()
+{
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let s = State::new(&mod_param);
+
+    assert_eq!( Err(IllegalReason::WrongCondition), s.check_action_reason(&mod_param, &Action::PreciseTouch) );
+    assert_eq!( Err(IllegalReason::InnerQuietNotMax), State { inner_quiet:9, .. s.clone() }.check_action_reason(&mod_param, &Action::TrainedFinesse) );
+    assert_eq!( Err(IllegalReason::TurnRestriction), State { turn:2, .. s.clone() }.check_action_reason(&mod_param, &Action::MuscleMemory) );
+    assert_eq!( Err(IllegalReason::WasteNotActive), State { waste_not:2, .. s.clone() }.check_action_reason(&mod_param, &Action::PrudentTouch) );
+    assert_eq!( Err(IllegalReason::HeartAndSoulUsed), State { heart_and_soul_used:true, .. s.clone() }.check_action_reason(&mod_param, &Action::HeartAndSoul) );
+
+    let required_cp = s.get_required_cp(&mod_param, &Action::Manipulation);
+    assert_eq!( Err(IllegalReason::InsufficientCp(required_cp)), State { cp:0, .. s.clone() }.check_action_reason(&mod_param, &Action::Manipulation) );
+    assert_eq!( Ok(()), s.check_action_reason(&mod_param, &Action::MuscleMemory) );
 }
 
+#[test]
+fn test_groundwork_efficiency()
+{
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let s = State::new(&mod_param);
+
+    // 耐久が消費耐久以上なら満額、足りなければ半減します
+    assert_eq!( 360, State { durability:20, .. s.clone() }.get_progress_efficiency(&mod_param, &Action::Groundwork) );
+    assert_eq!( 180, State { durability:10, .. s.clone() }.get_progress_efficiency(&mod_param, &Action::Groundwork) );
+
+    // 倹約中は消費耐久が10になるので、耐久10でも満額です
+    assert_eq!( 360, State { durability:10, waste_not:2, .. s.clone() }.get_progress_efficiency(&mod_param, &Action::Groundwork) );
+}
+
+#[test]
+fn test_durability_cost_condition()
+{
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let s = State::new(&mod_param);
+
+    assert_eq!( 10, s.get_durability_cost(&mod_param, &Action::BasicTouch) );
+    assert_eq!( 5, State { condition:Condition::Robust, .. s.clone() }.get_durability_cost(&mod_param, &Action::BasicTouch) );
+    assert_eq!( 5, State { condition:Condition::Solid, .. s.clone() }.get_durability_cost(&mod_param, &Action::BasicTouch) );
+
+    // 倹約と重なると切り上げで3になります
+    assert_eq!( 3, State { condition:Condition::Robust, waste_not:2, .. s.clone() }.get_durability_cost(&mod_param, &Action::BasicTouch) );
+}
@@ -0,0 +1,317 @@
+use xorshift::SeedableRng;
+
+use super::logic::{Action,State,Modifier,Condition};
+use super::setting::ModifierParameter;
+use super::condition::ConditionModel;
+use super::simulation::{Strategy,Decision,simulate,print_summary};
+
+// ゲーム内のマクロ1つに書ける最大行数です
+pub const MACRO_LINES: usize = 15;
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum MacroLanguage {
+    English,
+    Japanese,
+}
+
+impl MacroLanguage {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "en" => Ok(MacroLanguage::English),
+            "ja" => Ok(MacroLanguage::Japanese),
+            _ => Err("unknown macro language".to_string()),
+        }
+    }
+}
+
+impl argh::FromArgValue for MacroLanguage {
+    fn from_arg_value(value: &str) -> Result<Self, String> {
+        MacroLanguage::from_name(value)
+    }
+}
+
+// マクロの1手です。
+#[derive(Debug,Clone,PartialEq)]
+pub struct MacroStep
+{
+    pub action : Action,
+    pub wait : Option<u32>,               // <wait.N>の指定(無ければNone)
+}
+
+// "/ac 名前 <wait.3>" の形式の1行を読みます
+fn parse_line(line:&str) -> Result<Option<MacroStep>, String> {
+    let line = line.trim();
+    let (command, rest) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    };
+
+    // /ac と /action 以外(/echo や /macrolock など)は読み飛ばします
+    if command != "/ac" && command != "/action" {
+        return Ok(None)
+    }
+
+    // 末尾の<wait.N>や<se.N>を取り除きます
+    let mut name = rest;
+    let mut wait = None;
+    while let Some(i) = name.rfind('<') {
+        let tag = name[i..].trim_end_matches('>').trim_start_matches('<');
+        if let Some(x) = tag.strip_prefix("wait.") {
+            wait = Some(x.parse::<u32>().map_err(|_| format!("can't parse wait in \"{}\"", line))?);
+        }
+        name = name[..i].trim();
+    }
+    let name = name.trim_matches('"');
+
+    match Action::from_macro_name(name) {
+        Some(action) => Ok(Some(MacroStep { action, wait })),
+        None => Err(format!("unknown action \"{}\"", name)),
+    }
+}
+
+// マクロのテキストを読みます。複数のマクロを連結したテキストでも構いません
+pub fn parse_macro(text:&str) -> Result<Vec<MacroStep>, String> {
+    let mut steps = vec![];
+    for (i,line) in text.lines().enumerate() {
+        match parse_line(line) {
+            Ok(Some(x)) => steps.push(x),
+            Ok(None) => {},
+            Err(e) => return Err(format!("line {}: {}", i+1, e)),
+        }
+    }
+    Ok(steps)
+}
+
+// アクション列をマクロに変換します。MACRO_LINES行に収まるように分割し、echoが有効なら各マクロの最終行に完了通知を入れます
+pub fn emit_macro(actions:&[Action], lang:MacroLanguage, echo:bool) -> Vec<String> {
+    let per_macro = if echo { MACRO_LINES - 1 } else { MACRO_LINES };
+    let chunks : Vec<&[Action]> = actions.chunks(per_macro).collect();
+
+    chunks.iter().enumerate().map(|(i,chunk)| {
+        let mut lines : Vec<String> = chunk.iter().map(|a| {
+            let name = a.translate(lang);
+            let name = if name.contains(' ') { format!("\"{}\"", name) } else { name.to_string() };
+            format!("/ac {} <wait.{}>", name, a.default_wait())
+        }).collect();
+
+        if echo {
+            lines.push(format!("/echo Macro #{} complete <se.{}>", i+1, if i+1 == chunks.len() { 1 } else { 2 }));
+        }
+        lines.join("\n")
+    }).collect()
+}
+
+// convert-macro --echoと同じく、完了通知の行を含めて分割した時のマクロの数です
+fn count_macros(steps:&[MacroStep]) -> usize {
+    let actions : Vec<Action> = steps.iter().map(|x| x.action).collect();
+    emit_macro(&actions, MacroLanguage::English, true).len()
+}
+
+pub struct ValidateMacroParameter {
+    pub mod_param : ModifierParameter,
+    pub initial_state : State,
+    pub macro_text : String,
+}
+
+// マクロ実行中に実行できない手があった場合の扱いです
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum IllegalActionPolicy {
+    Skip,       // 読み飛ばして次の手に進む(ゲーム内マクロと同じ挙動です)
+    Fail,       // その時点で失敗とする
+}
+
+impl IllegalActionPolicy {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "skip" => Ok(IllegalActionPolicy::Skip),
+            "fail" => Ok(IllegalActionPolicy::Fail),
+            _ => Err("unknown illegal action policy".to_string()),
+        }
+    }
+}
+
+impl argh::FromArgValue for IllegalActionPolicy {
+    fn from_arg_value(value: &str) -> Result<Self, String> {
+        IllegalActionPolicy::from_name(value)
+    }
+}
+
+pub struct SimulateMacroParameter {
+    pub mod_param : ModifierParameter,
+    pub initial_state : State,
+    pub macro_text : String,
+    pub plays : usize,
+    pub illegal_action_policy : IllegalActionPolicy,
+    pub seed : u64,
+}
+
+// 固定マクロを順に実行する戦略です
+struct MacroStrategy
+{
+    actions : Vec<Action>,
+    index : usize,
+    policy : IllegalActionPolicy,
+}
+
+impl Strategy for MacroStrategy {
+    fn reset(&mut self) {
+        self.index = 0;
+    }
+
+    fn decide(&mut self, s:&State, mod_param:&ModifierParameter) -> Decision {
+        while let Some(a) = self.actions.get(self.index) {
+            self.index += 1;
+            if s.check_action(mod_param, a) {
+                return Decision::Run(*a)
+            }
+            if self.policy == IllegalActionPolicy::Fail {
+                return Decision::Abort
+            }
+        }
+        Decision::Stop
+    }
+}
+
+pub struct ConvertMacroParameter {
+    pub macro_text : String,
+    pub lang : MacroLanguage,
+    pub echo : bool,
+}
+
+// 状態は常に通常、成否判定は全て成功として実行し、実行できない手を報告します
+pub fn run_validate_macro( param:ValidateMacroParameter ) {
+    let steps = match parse_macro(&param.macro_text) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Parse error: {}", e);
+            return
+        },
+    };
+
+    let mut mod_param = param.mod_param.clone();
+    mod_param.condition_model = ConditionModel::Independent(vec![(Condition::Standard,1.0)]);
+    let seeds = [1u64, 1u64];
+    let mut modifier = Modifier { mod_param:mod_param.clone(), rng:SeedableRng::from_seed(&seeds[..]), forced_success:Some(true) };
+
+    let mut state = param.initial_state.clone();
+    let mut errors = 0;
+    for (i,step) in steps.iter().enumerate() {
+        if state.is_terminated() {
+            println!("#{} {}: craft is already finished", i+1, step.action.translate_en());
+            errors += 1;
+            continue;
+        }
+        if let Some(wait) = step.wait {
+            if wait < step.action.default_wait() {
+                println!("#{} {}: wait.{} is shorter than {}", i+1, step.action.translate_en(), wait, step.action.default_wait());
+            }
+        }
+        match state.check_action_reason(&mod_param, &step.action) {
+            Ok(()) => state = state.run_action(&mut modifier, &step.action),
+            Err(reason) => {
+                println!("#{} {}: {:?}", i+1, step.action.translate_en(), reason);
+                errors += 1;
+            },
+        }
+    }
+
+    println!("steps:{} errors:{} macros:{}", steps.len(), errors, count_macros(&steps));
+    println!("working:{}/{} quality:{}/{} durability:{} cp:{} completed:{}",
+        state.working, mod_param.max_working, state.quality, mod_param.max_quality, state.durability, state.cp, state.is_completed());
+}
+
+// マクロを指定回数実行して、完成率や品質の分布を表示します
+pub fn run_simulate_macro( param:SimulateMacroParameter ) {
+    let steps = match parse_macro(&param.macro_text) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Parse error: {}", e);
+            return
+        },
+    };
+
+    let mut strategy = MacroStrategy { actions:steps.iter().map(|x| x.action).collect(), index:0, policy:param.illegal_action_policy };
+    let results = simulate(&mut strategy, &param.mod_param, &param.initial_state, param.plays, param.seed);
+    print_summary(&results, &param.mod_param);
+}
+
+// 読み込んだマクロを指定の言語で出力し直します
+pub fn run_convert_macro( param:ConvertMacroParameter ) {
+    match parse_macro(&param.macro_text) {
+        Ok(steps) => {
+            let actions : Vec<Action> = steps.iter().map(|x| x.action).collect();
+            for (i,x) in emit_macro(&actions, param.lang, param.echo).iter().enumerate() {
+                println!("# Macro {}", i+1);
+                println!("{}", x);
+            }
+        },
+        Err(e) => println!("Parse error: {}", e),
+    }
+}
+
+#[test]
+fn test_parse_macro()
+{
+    let text = "/macrolock\n/ac \"Muscle Memory\" <wait.3>\n/ac 作業 <wait.3>\n/ac \"byregot's blessing\"\n/echo done <se.1>\n";
+    let steps = parse_macro(text).unwrap();
+    assert_eq!( vec![
+        MacroStep { action:Action::MuscleMemory, wait:Some(3) },
+        MacroStep { action:Action::BasicSynthesis, wait:Some(3) },
+        MacroStep { action:Action::ByregotsBlessing, wait:None },
+    ], steps );
+
+    assert!( parse_macro("/ac Unknown <wait.3>").is_err() );
+
+    // 14手+echoで分割されます
+    let actions = vec![Action::BasicTouch; 20];
+    let macros = emit_macro(&actions, MacroLanguage::English, true);
+    assert_eq!( 2, macros.len() );
+    assert_eq!( MACRO_LINES, macros[0].lines().count() );
+    assert_eq!( actions, parse_macro(&macros.join("\n")).unwrap().iter().map(|x| x.action).collect::<Vec<_>>() );
+}
+
+#[test]
+fn test_emit_macro_lines()
+{
+    // 完了通知の行を含めて、どのマクロも上限の行数に収まります
+    for n in 1..=50 {
+        for echo in &[false, true] {
+            let actions = vec![Action::BasicSynthesis; n];
+            let macros = emit_macro(&actions, MacroLanguage::Japanese, *echo);
+            assert!( macros.iter().all(|x| x.lines().count() <= MACRO_LINES), "n:{} echo:{}", n, echo );
+            assert!( !*echo || macros.iter().all(|x| x.lines().last().unwrap().starts_with("/echo")) );
+            assert_eq!( n, parse_macro(&macros.join("\n")).unwrap().len() );
+        }
+    }
+}
+
+#[test]
+fn test_count_macros()
+{
+    let steps = |n| vec![MacroStep { action:Action::BasicSynthesis, wait:None }; n];
+
+    // 完了通知の行があるので、1マクロに入るのは14手までです
+    assert_eq!( 1, count_macros(&steps(14)) );
+    assert_eq!( 2, count_macros(&steps(15)) );
+    assert_eq!( 3, count_macros(&steps(29)) );
+}
+
+#[test]
+fn test_illegal_action_policy()
+{
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let s = State::new(&mod_param);
+
+    // 2手目の確信は1ターン目にしか使えません
+    let actions = vec![Action::BasicTouch, Action::MuscleMemory, Action::BasicTouch];
+
+    // 読み飛ばす場合は残りの手を実行して、手順が尽きたら未完成のまま終わります
+    let mut strategy = MacroStrategy { actions:actions.clone(), index:0, policy:IllegalActionPolicy::Skip };
+    let results = simulate(&mut strategy, &mod_param, &s, 10, 1);
+    assert!( results.iter().all(|x| !x.aborted && !x.state.is_terminated() && x.state.turn == 3) );
+
+    // 失敗とする場合は、確信の手前で中断します
+    let mut strategy = MacroStrategy { actions:actions, index:0, policy:IllegalActionPolicy::Fail };
+    let results = simulate(&mut strategy, &mod_param, &s, 10, 1);
+    assert!( results.iter().all(|x| x.aborted && x.state.turn == 2) );
+}
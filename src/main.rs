@@ -17,15 +17,45 @@ mod executor;
 mod predictor;
 mod replay;
 mod setting;
-
-use setting::ModifierParameter;
+mod ruleset;
+mod condition;
+mod recipe;
+mod crafter;
+mod capability;
+mod start;
+mod solver;
+mod macros;
+mod simulation;
+mod rotation;
+mod seed;
+mod timing;
+mod target;
+mod reward;
+mod risk;
+mod analyze;
+
+use setting::{ModifierParameter,Preset};
 use argh::FromArgs;
 use selfplay::{WriterParameter,EpisodeParameter,SelfPlayParameter};
 use selector::Selector;
 use learner::{LearnerParameter};
 use benchmark::BenchmarkParameter;
-use network::NetworkType;
+use network::{NetworkType,ValueHead};
 use cui::{CuiParameter};
+use ruleset::{RulesetType,create_ruleset};
+use recipe::RecipeCatalog;
+use setting::CrafterStatus;
+use crafter::CrafterCatalog;
+use capability::Capability;
+use start::StartSetting;
+use timing::TimingModel;
+use reward::{RewardModelType,create_reward_model};
+use risk::RiskMode;
+use solver::SolverParameter;
+use analyze::{AnalyzeParameter,AnalyzeEvaluator};
+use replay::ReplayParameter;
+use rotation::SimulateRotationParameter;
+use macros::{MacroLanguage,IllegalActionPolicy,ValidateMacroParameter,SimulateMacroParameter,ConvertMacroParameter};
 
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(description="toplevel command")]
@@ -43,6 +73,12 @@ enum SubCommand {
     Benchmark(SubCommandBenchmark),
     Replay(SubCommandReplay),
     Cui(SubCommandCui),
+    Solve(SubCommandSolve),
+    Analyze(SubCommandAnalyze),
+    ValidateMacro(SubCommandValidateMacro),
+    SimulateMacro(SubCommandSimulateMacro),
+    SimulateRotation(SubCommandSimulateRotation),
+    ConvertMacro(SubCommandConvertMacro),
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
@@ -78,6 +114,45 @@ struct SubCommandEvaluator {
     #[argh(option, default="String::from(\"root\")", description="mysql user name")]
     mysql_user:String,
 
+    #[argh(option, description="crafting ruleset(shadowbringers/endwalker/dawntrail)")]
+    ruleset:Option<RulesetType>,
+
+    #[argh(option, description="recipe id or name in recipe file")]
+    recipe:Option<String>,
+
+    #[argh(option, default="Preset::Usouso", description="measured setting used without --recipe(usouso/ishgard4)")]
+    preset:Preset,
+
+    #[argh(option, default="String::from(\"data/recipes.json\")", description="recipe file(json/csv)")]
+    recipe_file:String,
+
+    #[argh(option, description="crafter profile(name or name:job)")]
+    crafter:Option<String>,
+
+    #[argh(option, description="food name")]
+    food:Option<String>,
+
+    #[argh(option, description="medicine name")]
+    medicine:Option<String>,
+
+    #[argh(option, default="String::from(\"data/crafters.json\")", description="crafter profile file")]
+    crafter_file:String,
+
+    #[argh(option, description="action timing and time bonus file(json)")]
+    timing:Option<String>,
+
+    #[argh(option, description="reward model(blend/hq/collectable/scoring/completion/throughput)")]
+    reward_model:Option<RewardModelType>,
+
+    #[argh(option, description="start setting file(json)")]
+    start:Option<String>,
+
+    #[argh(option, default="RiskMode::Mean", description="mcts objective(mean/threshold/quantile-ALPHA/cvar-ALPHA)")]
+    risk:RiskMode,
+
+    #[argh(option, description="random seed(random if omitted)")]
+    seed:Option<u64>,
+
     #[argh(switch, description="profile with flamegraph")]
     flamegraph: bool,
 }
@@ -124,6 +199,45 @@ struct SubCommandGenerator {
     #[argh(option, default="String::from(\"root\")", description="mysql user name")]
     mysql_user:String,
 
+    #[argh(option, description="crafting ruleset(shadowbringers/endwalker/dawntrail)")]
+    ruleset:Option<RulesetType>,
+
+    #[argh(option, description="recipe id or name in recipe file")]
+    recipe:Option<String>,
+
+    #[argh(option, default="Preset::Usouso", description="measured setting used without --recipe(usouso/ishgard4)")]
+    preset:Preset,
+
+    #[argh(option, default="String::from(\"data/recipes.json\")", description="recipe file(json/csv)")]
+    recipe_file:String,
+
+    #[argh(option, description="crafter profile(name or name:job)")]
+    crafter:Option<String>,
+
+    #[argh(option, description="food name")]
+    food:Option<String>,
+
+    #[argh(option, description="medicine name")]
+    medicine:Option<String>,
+
+    #[argh(option, default="String::from(\"data/crafters.json\")", description="crafter profile file")]
+    crafter_file:String,
+
+    #[argh(option, description="action timing and time bonus file(json)")]
+    timing:Option<String>,
+
+    #[argh(option, description="reward model(blend/hq/collectable/scoring/completion/throughput)")]
+    reward_model:Option<RewardModelType>,
+
+    #[argh(option, description="start setting file(json)")]
+    start:Option<String>,
+
+    #[argh(option, default="RiskMode::Mean", description="mcts objective(mean/threshold/quantile-ALPHA/cvar-ALPHA)")]
+    risk:RiskMode,
+
+    #[argh(option, description="random seed(random if omitted)")]
+    seed:Option<u64>,
+
     #[argh(switch, description="profile with flamegraph")]
     flamegraph: bool,
 }
@@ -140,7 +254,7 @@ struct SubCommandLearner {
     #[argh(option, default="String::from(\"root\")", description="mysql user name")]
     mysql_user:String,
 
-    #[argh(option, default="NetworkType::FullyConnected(4,128)", description="network type")]
+    #[argh(option, default="NetworkType::FullyConnected(4,128,ValueHead::Scalar)", description="network type(e.g. fc-4-128, residual-4-128, append -dist for distributional value head)")]
     network_type: NetworkType,
 
     #[argh(switch, description="profile with flamegraph")]
@@ -155,18 +269,344 @@ struct SubCommandBenchmark {
 
     #[argh(option, default="16384", description="plays per write")]
     plays_per_write:usize,
+
+    #[argh(option, description="crafting ruleset(shadowbringers/endwalker/dawntrail)")]
+    ruleset:Option<RulesetType>,
+
+    #[argh(option, description="recipe id or name in recipe file")]
+    recipe:Option<String>,
+
+    #[argh(option, default="Preset::Usouso", description="measured setting used without --recipe(usouso/ishgard4)")]
+    preset:Preset,
+
+    #[argh(option, default="String::from(\"data/recipes.json\")", description="recipe file(json/csv)")]
+    recipe_file:String,
+
+    #[argh(option, description="crafter profile(name or name:job)")]
+    crafter:Option<String>,
+
+    #[argh(option, description="food name")]
+    food:Option<String>,
+
+    #[argh(option, description="medicine name")]
+    medicine:Option<String>,
+
+    #[argh(option, default="String::from(\"data/crafters.json\")", description="crafter profile file")]
+    crafter_file:String,
+
+    #[argh(option, description="action timing and time bonus file(json)")]
+    timing:Option<String>,
+
+    #[argh(option, description="reward model(blend/hq/collectable/scoring/completion/throughput)")]
+    reward_model:Option<RewardModelType>,
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(subcommand, name="replay", description="replay record")]
 struct SubCommandReplay {
     #[argh(positional, description="record name")]
-    record_names: Vec<String>
+    record_names: Vec<String>,
+
+    #[argh(option, description="recipe id or name in recipe file")]
+    recipe:Option<String>,
+
+    #[argh(option, default="Preset::Usouso", description="measured setting used without --recipe(usouso/ishgard4)")]
+    preset:Preset,
+
+    #[argh(option, default="String::from(\"data/recipes.json\")", description="recipe file(json/csv)")]
+    recipe_file:String,
+
+    #[argh(option, description="rerun the episode recorded with this seed")]
+    episode_seed:Option<u64>,
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(subcommand, name="cui", description="CUI for crafting")]
 struct SubCommandCui {
+    #[argh(option, description="crafting ruleset(shadowbringers/endwalker/dawntrail)")]
+    ruleset:Option<RulesetType>,
+
+    #[argh(option, description="recipe id or name in recipe file")]
+    recipe:Option<String>,
+
+    #[argh(option, default="Preset::Usouso", description="measured setting used without --recipe(usouso/ishgard4)")]
+    preset:Preset,
+
+    #[argh(option, default="String::from(\"data/recipes.json\")", description="recipe file(json/csv)")]
+    recipe_file:String,
+
+    #[argh(option, description="crafter profile(name or name:job)")]
+    crafter:Option<String>,
+
+    #[argh(option, description="food name")]
+    food:Option<String>,
+
+    #[argh(option, description="medicine name")]
+    medicine:Option<String>,
+
+    #[argh(option, default="String::from(\"data/crafters.json\")", description="crafter profile file")]
+    crafter_file:String,
+
+    #[argh(option, description="action timing and time bonus file(json)")]
+    timing:Option<String>,
+
+    #[argh(option, description="reward model(blend/hq/collectable/scoring/completion/throughput)")]
+    reward_model:Option<RewardModelType>,
+
+    #[argh(option, description="start setting file(json)")]
+    start:Option<String>,
+
+    #[argh(option, description="random seed(random if omitted)")]
+    seed:Option<u64>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name="solve", description="solve exactly by expectimax")]
+struct SubCommandSolve {
+    #[argh(option, description="crafting ruleset(shadowbringers/endwalker/dawntrail)")]
+    ruleset:Option<RulesetType>,
+
+    #[argh(option, description="recipe id or name in recipe file")]
+    recipe:Option<String>,
+
+    #[argh(option, default="Preset::Usouso", description="measured setting used without --recipe(usouso/ishgard4)")]
+    preset:Preset,
+
+    #[argh(option, default="String::from(\"data/recipes.json\")", description="recipe file(json/csv)")]
+    recipe_file:String,
+
+    #[argh(option, description="crafter profile(name or name:job)")]
+    crafter:Option<String>,
+
+    #[argh(option, description="food name")]
+    food:Option<String>,
+
+    #[argh(option, description="medicine name")]
+    medicine:Option<String>,
+
+    #[argh(option, default="String::from(\"data/crafters.json\")", description="crafter profile file")]
+    crafter_file:String,
+
+    #[argh(option, description="action timing and time bonus file(json)")]
+    timing:Option<String>,
+
+    #[argh(option, description="reward model(blend/hq/collectable/scoring/completion/throughput)")]
+    reward_model:Option<RewardModelType>,
+
+    #[argh(option, description="start setting file(json)")]
+    start:Option<String>,
+
+    #[argh(option, default="30", description="treat as failure after this turn")]
+    max_turn:u32,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name="analyze", description="dump mcts statistics for a state")]
+struct SubCommandAnalyze {
+    #[argh(positional, description="state file(json, omitted fields are initial values)")]
+    state_file: String,
+
+    #[argh(option, description="crafting ruleset(shadowbringers/endwalker/dawntrail)")]
+    ruleset:Option<RulesetType>,
+
+    #[argh(option, description="recipe id or name in recipe file")]
+    recipe:Option<String>,
+
+    #[argh(option, default="Preset::Usouso", description="measured setting used without --recipe(usouso/ishgard4)")]
+    preset:Preset,
+
+    #[argh(option, default="String::from(\"data/recipes.json\")", description="recipe file(json/csv)")]
+    recipe_file:String,
+
+    #[argh(option, description="crafter profile(name or name:job)")]
+    crafter:Option<String>,
+
+    #[argh(option, description="food name")]
+    food:Option<String>,
+
+    #[argh(option, description="medicine name")]
+    medicine:Option<String>,
+
+    #[argh(option, default="String::from(\"data/crafters.json\")", description="crafter profile file")]
+    crafter_file:String,
+
+    #[argh(option, description="action timing and time bonus file(json)")]
+    timing:Option<String>,
+
+    #[argh(option, description="reward model(blend/hq/collectable/scoring/completion/throughput)")]
+    reward_model:Option<RewardModelType>,
+
+    #[argh(option, description="local weights file")]
+    weights:Option<String>,
+
+    #[argh(option, description="model name to download")]
+    model:Option<String>,
+
+    #[argh(option, default="NetworkType::FullyConnected(4,128,ValueHead::Scalar)", description="network type of weights or model")]
+    network_type:NetworkType,
+
+    #[argh(option, default="10", description="random playouts per leaf when no network is given")]
+    rollouts:u32,
+
+    #[argh(option, default="1000", description="mcts simulation num")]
+    mcts_simulation_num:u32,
+
+    #[argh(option, default="RiskMode::Mean", description="mcts objective(mean/threshold/quantile-ALPHA/cvar-ALPHA)")]
+    risk:RiskMode,
+
+    #[argh(switch, description="output as json")]
+    json:bool,
+
+    #[argh(option, description="random seed(random if omitted)")]
+    seed:Option<u64>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name="validate-macro", description="validate in-game macro against recipe")]
+struct SubCommandValidateMacro {
+    #[argh(positional, description="macro file")]
+    macro_file: String,
+
+    #[argh(option, description="crafting ruleset(shadowbringers/endwalker/dawntrail)")]
+    ruleset:Option<RulesetType>,
+
+    #[argh(option, description="recipe id or name in recipe file")]
+    recipe:Option<String>,
+
+    #[argh(option, default="Preset::Usouso", description="measured setting used without --recipe(usouso/ishgard4)")]
+    preset:Preset,
+
+    #[argh(option, default="String::from(\"data/recipes.json\")", description="recipe file(json/csv)")]
+    recipe_file:String,
+
+    #[argh(option, description="crafter profile(name or name:job)")]
+    crafter:Option<String>,
+
+    #[argh(option, description="food name")]
+    food:Option<String>,
+
+    #[argh(option, description="medicine name")]
+    medicine:Option<String>,
+
+    #[argh(option, default="String::from(\"data/crafters.json\")", description="crafter profile file")]
+    crafter_file:String,
+
+    #[argh(option, description="action timing and time bonus file(json)")]
+    timing:Option<String>,
+
+    #[argh(option, description="reward model(blend/hq/collectable/scoring/completion/throughput)")]
+    reward_model:Option<RewardModelType>,
+
+    #[argh(option, description="start setting file(json)")]
+    start:Option<String>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name="simulate-macro", description="evaluate in-game macro by monte carlo simulation")]
+struct SubCommandSimulateMacro {
+    #[argh(positional, description="macro file")]
+    macro_file: String,
+
+    #[argh(option, description="crafting ruleset(shadowbringers/endwalker/dawntrail)")]
+    ruleset:Option<RulesetType>,
+
+    #[argh(option, description="recipe id or name in recipe file")]
+    recipe:Option<String>,
+
+    #[argh(option, default="Preset::Usouso", description="measured setting used without --recipe(usouso/ishgard4)")]
+    preset:Preset,
+
+    #[argh(option, default="String::from(\"data/recipes.json\")", description="recipe file(json/csv)")]
+    recipe_file:String,
+
+    #[argh(option, description="crafter profile(name or name:job)")]
+    crafter:Option<String>,
+
+    #[argh(option, description="food name")]
+    food:Option<String>,
+
+    #[argh(option, description="medicine name")]
+    medicine:Option<String>,
+
+    #[argh(option, default="String::from(\"data/crafters.json\")", description="crafter profile file")]
+    crafter_file:String,
+
+    #[argh(option, description="action timing and time bonus file(json)")]
+    timing:Option<String>,
+
+    #[argh(option, description="reward model(blend/hq/collectable/scoring/completion/throughput)")]
+    reward_model:Option<RewardModelType>,
+
+    #[argh(option, description="start setting file(json)")]
+    start:Option<String>,
+
+    #[argh(option, default="10000", description="number of plays")]
+    plays:usize,
+
+    #[argh(option, default="IllegalActionPolicy::Skip", description="what to do on illegal action(skip/fail)")]
+    on_illegal:IllegalActionPolicy,
+
+    #[argh(option, description="random seed(random if omitted)")]
+    seed:Option<u64>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name="simulate-rotation", description="evaluate conditional rotation by monte carlo simulation")]
+struct SubCommandSimulateRotation {
+    #[argh(positional, description="rotation file")]
+    rotation_file: String,
+
+    #[argh(option, description="crafting ruleset(shadowbringers/endwalker/dawntrail)")]
+    ruleset:Option<RulesetType>,
+
+    #[argh(option, description="recipe id or name in recipe file")]
+    recipe:Option<String>,
+
+    #[argh(option, default="Preset::Usouso", description="measured setting used without --recipe(usouso/ishgard4)")]
+    preset:Preset,
+
+    #[argh(option, default="String::from(\"data/recipes.json\")", description="recipe file(json/csv)")]
+    recipe_file:String,
+
+    #[argh(option, description="crafter profile(name or name:job)")]
+    crafter:Option<String>,
+
+    #[argh(option, description="food name")]
+    food:Option<String>,
+
+    #[argh(option, description="medicine name")]
+    medicine:Option<String>,
+
+    #[argh(option, default="String::from(\"data/crafters.json\")", description="crafter profile file")]
+    crafter_file:String,
+
+    #[argh(option, description="action timing and time bonus file(json)")]
+    timing:Option<String>,
+
+    #[argh(option, description="reward model(blend/hq/collectable/scoring/completion/throughput)")]
+    reward_model:Option<RewardModelType>,
+
+    #[argh(option, description="start setting file(json)")]
+    start:Option<String>,
+
+    #[argh(option, default="10000", description="number of plays")]
+    plays:usize,
+
+    #[argh(option, description="random seed(random if omitted)")]
+    seed:Option<u64>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name="convert-macro", description="convert in-game macro language and split per 15 lines")]
+struct SubCommandConvertMacro {
+    #[argh(positional, description="macro file")]
+    macro_file: String,
+
+    #[argh(option, default="MacroLanguage::English", description="output language(en/ja)")]
+    lang: MacroLanguage,
+
+    #[argh(switch, description="add echo line to each macro")]
+    echo: bool,
 }
 
 fn get_selector( ucb1:Option<f64>, optimistic:Option<usize>, greedy:Option<usize> ) -> Option<Selector> {
@@ -184,6 +624,69 @@ fn get_selector( ucb1:Option<f64>, optimistic:Option<usize>, greedy:Option<usize
     }
 }
 
+// 製作者が指定されていれば食事・薬込みのステータスを計算します
+fn get_crafter_status( crafter:&Option<String>, food:&Option<String>, medicine:&Option<String>, crafter_file:&str ) -> Option<CrafterStatus> {
+    let key = crafter.as_ref()?;
+    let catalog = CrafterCatalog::load(crafter_file).unwrap();
+    let job = catalog.find_job(key).unwrap();
+
+    let consumables : Vec<_> = food.iter().chain(medicine.iter()).map(|x| catalog.find_consumable(x).unwrap()).collect();
+    Some(job.effective_status(&consumables))
+}
+
+// レシピが指定されていればレシピファイルから、無ければプリセットから作成します
+// 製作者が指定されていなければウソウソの泉の計測時のステータスを使います
+// ルールセット・所要時間の設定・報酬モデルが指定されていればそちらで上書きします
+fn get_mod_param( ruleset:Option<RulesetType>, preset:Preset, recipe:&Option<String>, recipe_file:&str, crafter:Option<CrafterStatus>, timing:&Option<String>, reward_model:Option<RewardModelType> ) -> ModifierParameter {
+    let mut mod_param = match recipe {
+        Some(key) => {
+            let catalog = RecipeCatalog::load(recipe_file).unwrap();
+            let recipe = catalog.find(key).unwrap_or_else(|| panic!("recipe {} is not found in {}", key, recipe_file));
+            recipe.create_mod_param(&crafter.unwrap_or_else(CrafterStatus::new_fountain_of_usouso))
+        },
+        None => {
+            // 計測値のテーブルなのでCPとレベル・スペシャリストだけ反映します
+            let mut mod_param = ModifierParameter::new_from_preset(preset);
+            if let Some(x) = crafter {
+                mod_param.max_cp = x.cp;
+                mod_param.capability = Capability { level:x.level, specialist:x.specialist };
+            }
+            mod_param
+        },
+    };
+    if let Some(x) = ruleset {
+        mod_param.ruleset = create_ruleset(x);
+    }
+    if let Some(path) = timing {
+        mod_param.timing = TimingModel::load(path).unwrap();
+    }
+    if let Some(x) = reward_model {
+        if x == RewardModelType::Collectable && mod_param.collectable.is_none() {
+            panic!("collectable reward model requires collectable tiers in the recipe");
+        }
+        if x == RewardModelType::Scoring && mod_param.scoring.is_none() {
+            panic!("scoring reward model requires a scoring table in the recipe");
+        }
+        mod_param.reward_model = create_reward_model(x);
+    }
+    mod_param
+}
+
+// 開始状態の設定があればそこから、無ければ通常の開始状態を作ります
+fn get_initial_state( start:&Option<String>, mod_param:&ModifierParameter ) -> logic::State {
+    match start {
+        Some(path) => StartSetting::load(path).and_then(|x| x.build(mod_param)).unwrap(),
+        None => logic::State::new(mod_param),
+    }
+}
+
+// シードが指定されていなければ時刻から作り、再現できるように表示します
+fn get_seed( seed:Option<u64> ) -> u64 {
+    let seed = seed.unwrap_or_else(seed::new_seed);
+    eprintln!("seed: {}", seed);
+    seed
+}
+
 fn with_flamegraph<F: FnOnce()>( f:F ) {
     let guard = pprof::ProfilerGuard::new(100).unwrap();
     f();
@@ -194,13 +697,18 @@ fn with_flamegraph<F: FnOnce()>( f:F ) {
 }
 
 fn cmd_evaluator( args:SubCommandEvaluator ) {
+    let mod_param = get_mod_param(args.ruleset, args.preset, &args.recipe, &args.recipe_file, get_crafter_status(&args.crafter, &args.food, &args.medicine, &args.crafter_file), &args.timing, args.reward_model);
+    let initial_state = get_initial_state(&args.start, &mod_param);
     let param = SelfPlayParameter {
         episode_param: EpisodeParameter {
-            mod_param:ModifierParameter::new_fountain_of_usouso(),
+            mod_param:mod_param,
+            initial_state:initial_state,
             mcts_simulation_num:args.mcts_simulation_num,
             alpha:0.15,
             eps:0.0,
             start_greedy_turn:0,
+            seed:get_seed(args.seed),
+            risk_mode:args.risk,
         },
         selector:get_selector(args.ucb1, args.optimistic, args.greedy).unwrap_or(Selector::Optimistic(10)),
         plays_per_write:args.plays_per_write,
@@ -221,13 +729,18 @@ fn cmd_evaluator( args:SubCommandEvaluator ) {
 }
 
 fn cmd_generator( args:SubCommandGenerator ) {
+    let mod_param = get_mod_param(args.ruleset, args.preset, &args.recipe, &args.recipe_file, get_crafter_status(&args.crafter, &args.food, &args.medicine, &args.crafter_file), &args.timing, args.reward_model);
+    let initial_state = get_initial_state(&args.start, &mod_param);
     let param = SelfPlayParameter {
         episode_param: EpisodeParameter {
-            mod_param:ModifierParameter::new_fountain_of_usouso(),
+            mod_param:mod_param,
+            initial_state:initial_state,
             mcts_simulation_num:args.mcts_simulation_num,
             alpha:args.alpha,
             eps:args.eps,
             start_greedy_turn:args.start_greedy_turn,
+            seed:get_seed(args.seed),
+            risk_mode:args.risk,
         },
         selector:get_selector(args.ucb1, args.optimistic, args.greedy).unwrap_or(Selector::Greedy(50)),
         plays_per_write:args.plays_per_write,
@@ -265,7 +778,7 @@ fn cmd_learner( args:SubCommandLearner ) {
 
 fn cmd_benchmark( args:SubCommandBenchmark ) {
     let param = BenchmarkParameter {
-        mod_param:ModifierParameter::new_fountain_of_usouso(),
+        mod_param:get_mod_param(args.ruleset, args.preset, &args.recipe, &args.recipe_file, get_crafter_status(&args.crafter, &args.food, &args.medicine, &args.crafter_file), &args.timing, args.reward_model),
         batch_size:args.batch_size,
         plays_per_write:args.plays_per_write,
     };
@@ -274,17 +787,113 @@ fn cmd_benchmark( args:SubCommandBenchmark ) {
 }
 
 fn cmd_replay( args: SubCommandReplay ) {
-    replay::run_replay( args.record_names );
+    let param = ReplayParameter {
+        record_names:args.record_names,
+        mod_param:get_mod_param(None, args.preset, &args.recipe, &args.recipe_file, None, &None, None),
+        episode_seed:args.episode_seed,
+    };
+
+    replay::run_replay(param);
 }
 
-fn cmd_cui( _args:SubCommandCui ) {
+fn cmd_cui( args:SubCommandCui ) {
+    let mod_param = get_mod_param(args.ruleset, args.preset, &args.recipe, &args.recipe_file, get_crafter_status(&args.crafter, &args.food, &args.medicine, &args.crafter_file), &args.timing, args.reward_model);
+    let initial_state = get_initial_state(&args.start, &mod_param);
     let param = CuiParameter {
-        mod_param:ModifierParameter::new_fountain_of_usouso(),
+        mod_param:mod_param,
+        initial_state:initial_state,
+        seed:get_seed(args.seed),
     };
 
     cui::run_cui(param);
 }
 
+fn cmd_solve( args:SubCommandSolve ) {
+    let mod_param = get_mod_param(args.ruleset, args.preset, &args.recipe, &args.recipe_file, get_crafter_status(&args.crafter, &args.food, &args.medicine, &args.crafter_file), &args.timing, args.reward_model);
+    let initial_state = get_initial_state(&args.start, &mod_param);
+    let param = SolverParameter {
+        mod_param:mod_param,
+        initial_state:initial_state,
+        max_turn:args.max_turn,
+    };
+
+    solver::run_solver(param);
+}
+
+fn cmd_analyze( args:SubCommandAnalyze ) {
+    let mod_param = get_mod_param(args.ruleset, args.preset, &args.recipe, &args.recipe_file, get_crafter_status(&args.crafter, &args.food, &args.medicine, &args.crafter_file), &args.timing, args.reward_model);
+    let state_json = serde_json::from_str(&std::fs::read_to_string(&args.state_file).unwrap()).unwrap();
+    let state = StartSetting { materials:None, state:Some(state_json) }.build(&mod_param).unwrap();
+    let evaluator = match (args.weights, args.model) {
+        (Some(x), _) => AnalyzeEvaluator::Weights(x),
+        (None, Some(x)) => AnalyzeEvaluator::Model(x),
+        (None, None) => AnalyzeEvaluator::Rollout(args.rollouts),
+    };
+    let param = AnalyzeParameter {
+        mod_param:mod_param,
+        state:state,
+        evaluator:evaluator,
+        network_type:args.network_type,
+        mcts_simulation_num:args.mcts_simulation_num,
+        risk_mode:args.risk,
+        json:args.json,
+        seed:get_seed(args.seed),
+    };
+
+    analyze::run_analyze(param);
+}
+
+fn cmd_validate_macro( args:SubCommandValidateMacro ) {
+    let mod_param = get_mod_param(args.ruleset, args.preset, &args.recipe, &args.recipe_file, get_crafter_status(&args.crafter, &args.food, &args.medicine, &args.crafter_file), &args.timing, args.reward_model);
+    let initial_state = get_initial_state(&args.start, &mod_param);
+    let param = ValidateMacroParameter {
+        mod_param:mod_param,
+        initial_state:initial_state,
+        macro_text:std::fs::read_to_string(&args.macro_file).unwrap(),
+    };
+
+    macros::run_validate_macro(param);
+}
+
+fn cmd_simulate_macro( args:SubCommandSimulateMacro ) {
+    let mod_param = get_mod_param(args.ruleset, args.preset, &args.recipe, &args.recipe_file, get_crafter_status(&args.crafter, &args.food, &args.medicine, &args.crafter_file), &args.timing, args.reward_model);
+    let initial_state = get_initial_state(&args.start, &mod_param);
+    let param = SimulateMacroParameter {
+        mod_param:mod_param,
+        initial_state:initial_state,
+        macro_text:std::fs::read_to_string(&args.macro_file).unwrap(),
+        plays:args.plays,
+        illegal_action_policy:args.on_illegal,
+        seed:get_seed(args.seed),
+    };
+
+    macros::run_simulate_macro(param);
+}
+
+fn cmd_simulate_rotation( args:SubCommandSimulateRotation ) {
+    let mod_param = get_mod_param(args.ruleset, args.preset, &args.recipe, &args.recipe_file, get_crafter_status(&args.crafter, &args.food, &args.medicine, &args.crafter_file), &args.timing, args.reward_model);
+    let initial_state = get_initial_state(&args.start, &mod_param);
+    let param = SimulateRotationParameter {
+        mod_param:mod_param,
+        initial_state:initial_state,
+        rotation_text:std::fs::read_to_string(&args.rotation_file).unwrap(),
+        plays:args.plays,
+        seed:get_seed(args.seed),
+    };
+
+    rotation::run_simulate_rotation(param);
+}
+
+fn cmd_convert_macro( args:SubCommandConvertMacro ) {
+    let param = ConvertMacroParameter {
+        macro_text:std::fs::read_to_string(&args.macro_file).unwrap(),
+        lang:args.lang,
+        echo:args.echo,
+    };
+
+    macros::run_convert_macro(param);
+}
+
 fn main() {
     let cmdline: TopLevel = argh::from_env();
 
@@ -295,5 +904,11 @@ fn main() {
         SubCommand::Benchmark(x) => cmd_benchmark(x),
         SubCommand::Replay(x) => cmd_replay(x),
         SubCommand::Cui(x) => cmd_cui(x),
+        SubCommand::Solve(x) => cmd_solve(x),
+        SubCommand::Analyze(x) => cmd_analyze(x),
+        SubCommand::ValidateMacro(x) => cmd_validate_macro(x),
+        SubCommand::SimulateMacro(x) => cmd_simulate_macro(x),
+        SubCommand::SimulateRotation(x) => cmd_simulate_rotation(x),
+        SubCommand::ConvertMacro(x) => cmd_convert_macro(x),
     }
 }
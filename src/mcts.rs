@@ -2,13 +2,18 @@
 use super::logic::{State,Action,Modifier,ACTION_NUM};
 use super::setting::ModifierParameter;
 use super::predictor::*;
+use super::risk::{RiskMode,ValueHistogram,RISK_BINS,add_to_histogram,add_histogram};
 use num::FromPrimitive;
+use serde::Serialize;
 use xorshift::{Rng,Xorshift128};
 use rand::prelude::*;
 use rand::distributions::Dirichlet;
 
 pub type ActionVector = [f32;ACTION_NUM];
 
+// 方策・評価値と、分布型バリューヘッドなら評価値の分布です
+pub type Prediction = (ActionVector,f32,Option<ValueHistogram>);
+
 #[allow(non_snake_case)]
 #[derive(Debug)]
 struct Node
@@ -21,6 +26,9 @@ struct Node
 
     // 各アクションを取ったときの、子ノードの評価値の総和
     W : ActionVector,
+
+    // 各アクションを取ったときの、子ノードの評価値の分布。分位点・CVaRモードの時だけ持ちます
+    H : Vec<ValueHistogram>,
 }
 
 pub struct MCTSContext
@@ -37,6 +45,9 @@ pub struct MCTSContext
     // UCTの定数
     c_puct: f32,
 
+    // 最大化する指標。バックアップと選択の両方で使います
+    risk_mode: RiskMode,
+
     // ノード一覧
     nodes: HashMap<State,Node>,
 
@@ -47,6 +58,17 @@ pub struct MCTSContext
     graph_filename: String,
 }
 
+// 解析結果の1行です
+#[derive(Debug,Serialize)]
+pub struct ActionAnalysis
+{
+    pub action : Action,
+    pub n : f32,                          // 訪問回数
+    pub q : f32,                          // 行動価値(リスクモードに従います)
+    pub p : f32,                          // 事前確率(ディリクレノイズ込み)
+    pub pv : Vec<Action>,                 // 読み筋
+}
+
 enum SearchResult {
     Expand(State), // 途中の場合
     Reward(f32),   // 報酬がもらえる場合
@@ -56,7 +78,7 @@ impl State {
     // 現実にあり得ないパターンを除外します。
     // 初手インナークワイエット使うくらいなら真価を使うとか、そういう基本的な手だけ対策します。
     // あと「作業で辿りつける場合は最終確認は無効」とかも削ってよいかもしれません。
-    fn check_action_ex(&self, a:&Action) -> bool {
+    fn check_action_ex(&self, mod_param:&ModifierParameter, a:&Action) -> bool {
         if self.turn == 1 {
             // 1ターン目は確信か真価に限定します。
             // 流石にこれ以外のスタートパターンは現実的に存在しないため、これだけは無視します
@@ -70,27 +92,28 @@ impl State {
             // もし最終確認が有効な場合、最終確認を新たに使うことはあり得ません。これは何の役にも立たずCPだけを消費します。
             false
         }
-        else if self.working < 5000 && *a == Action::FinalAppraisal {
+        else if self.working + mod_param.advance_table.working_advance(500, true, true, true) < mod_param.max_working && *a == Action::FinalAppraisal {
             // もし１手で完成に辿りつけない作業工数である場合、最終確認を使うことはあり得ません。次のターンで使えば良いためです。
+            // 高進捗・ヴェネレーション・確信が全て乗った突貫作業を１手の上限として判定します
             false
         }
         else {
-            self.check_action(a)
+            self.check_action(mod_param, a)
         }
     }
 }
 
 #[allow(non_snake_case)]
-fn get_scores(c_puct:f32, s:&State, node:&Node) -> ActionVector {
+fn get_scores(c_puct:f32, risk_mode:&RiskMode, s:&State, node:&Node, mod_param:&ModifierParameter) -> ActionVector {
     let mut scores = [0.0;ACTION_NUM];
 
     let sum_N : f32 = node.N.iter().sum();
     let sum_N_sqrt = sum_N.sqrt();
 
     for a in 0..ACTION_NUM {
-        if s.check_action_ex(&Action::from_usize(a).unwrap()) {
+        if s.check_action_ex(mod_param, &Action::from_usize(a).unwrap()) {
             let U = c_puct * node.P[a] * sum_N_sqrt / (1.0+node.N[a]);
-            let Q = if node.N[a] != 0.0 { node.W[a] / node.N[a] } else { 0.0 };
+            let Q = risk_mode.action_value(node.W[a], node.N[a], node.H.get(a));
             scores[a] = U+Q;
         }
         else {
@@ -108,35 +131,6 @@ fn get_mcts_policy( v:&ActionVector ) -> ActionVector {
     r
 }
 
-// [a,b]区間でcがどの位置にいるかを取得します。
-fn lerp_clip( a:f32, b:f32, c:f32 ) -> f32 {
-    let t = (c-a)/(b-a);
-    t.max(0.0).min(1.0)
-}
-
-// 報酬関数です。
-pub fn get_reward(s:&State,mod_param:&ModifierParameter) -> f32 {
-    if s.is_destroyed() {
-        0.0
-    }
-    else {
-        // 品質[0,1]
-        let quality_reward = s.quality as f32 / mod_param.max_quality as f32;
-
-        // ターン(タイム)ボーナス[0,1]
-        let time_reward = lerp_clip(150.0,50.0,s.time as f32);
-
-        // max品質ボーナス
-        let threshold_reward = if s.quality >= mod_param.bonus_threshold { 1.0 } else { 0.0 };
-
-        // 品質ボーナス割合は計算で求めます
-        let quality_t = 1.0 - mod_param.bonus_time_t - mod_param.bonus_threshold_t;
-
-        // 結果は重みづけでマージ
-        quality_reward*quality_t + time_reward*mod_param.bonus_time_t + threshold_reward*mod_param.bonus_threshold_t
-    }
-}
-
 fn select_max_indices(mcts_policy:&ActionVector) -> Vec<usize> {
     // Rustでf32やf64の配列の最大値を得る方法
     // https://qiita.com/lo48576/items/343ca40a03c3b86b67cb
@@ -185,9 +179,10 @@ pub fn select_action_greedy(mcts_policy:&ActionVector, rng:&mut Xorshift128) ->
 
 impl MCTSContext {
 
-    pub fn new( c_puct:f32, alpha:f32, eps:f32, predict_queue:PredictQueue, graph_filename:String ) -> MCTSContext {
+    pub fn new( c_puct:f32, alpha:f32, eps:f32, risk_mode:RiskMode, predict_queue:PredictQueue, graph_filename:String ) -> MCTSContext {
         MCTSContext {
             c_puct: c_puct,
+            risk_mode: risk_mode,
             alpha: alpha,
             eps: eps,
             nodes: HashMap::new(),
@@ -197,7 +192,7 @@ impl MCTSContext {
     }
 
     #[allow(non_snake_case)]
-    fn add_dirichlet_noise(&mut self, s:&State, _modifier:&mut Modifier) {
+    fn add_dirichlet_noise(&mut self, s:&State, modifier:&mut Modifier) {
         if self.eps > 0.0 {
             // ノードを探し出します。expandしてますので絶対に成功します。
             let mut node = self.nodes.get_mut(s).unwrap();
@@ -207,14 +202,16 @@ impl MCTSContext {
             let mut valid_actions : Vec<usize> = vec!{};
 
             for a in 0..ACTION_NUM {
-                if s.check_action_ex(&Action::from_usize(a).unwrap()) {
+                if s.check_action_ex(&modifier.mod_param, &Action::from_usize(a).unwrap()) {
                     valid_actions.push(a);
                 }
             }
 
             // ディリクレ分布を求めます
             let dirichlet = Dirichlet::new_with_param(self.alpha as f64, valid_actions.len());
-            let samples = dirichlet.sample(&mut rand::thread_rng()); // TODO: Xorshiftが使えなかった
+            // Xorshiftは直接使えないので、Xorshiftから引いた値でStdRngを初期化して再現性を保ちます
+            let mut noise_rng = rand::rngs::StdRng::seed_from_u64(modifier.rng.next_u64());
+            let samples = dirichlet.sample(&mut noise_rng);
 
             // ノイズを対象インデックスに足す
             for i in 0..valid_actions.len() {
@@ -229,10 +226,10 @@ impl MCTSContext {
         let mut path = vec!{};
         loop {
             if s.is_terminated() {
-                return (path,SearchResult::Reward(get_reward(&s,&modifier.mod_param)));
+                return (path,SearchResult::Reward(self.risk_mode.terminal_value(&s,&modifier.mod_param)));
             }
             else if let Some(node) = self.nodes.get(&s) {
-                let scores = get_scores(self.c_puct, &s, node);
+                let scores = get_scores(self.c_puct, &self.risk_mode, &s, node, &modifier.mod_param);
                 let a = choose_max_index(&scores, &mut modifier.rng);
                 let ns = s.run_action(modifier, &Action::from_usize(a).unwrap());
                 path.push((s,a));
@@ -251,15 +248,22 @@ impl MCTSContext {
             N: [0.0;ACTION_NUM],
             W: [0.0;ACTION_NUM],
             P: nn_policy,
+            H: if self.risk_mode.needs_histogram() { vec![[0.0;RISK_BINS];ACTION_NUM] } else { vec![] },
         });
     }
 
-    // 評価値を足します。
-    fn add_value(&mut self, path:&Vec<(State,usize)>, v:f32) {
+    // 評価値を足します。分布が予測されていれば、ヒストグラムには期待値ではなく分布を足します
+    fn add_value(&mut self, path:&Vec<(State,usize)>, v:f32, distribution:Option<&ValueHistogram>) {
         for (s,a) in path {
             let node = self.nodes.get_mut(s).unwrap();
             node.W[*a] += v;
             node.N[*a] += 1.0;
+            if let Some(h) = node.H.get_mut(*a) {
+                match distribution {
+                    Some(d) => add_histogram(h, d),
+                    None => add_to_histogram(h, v),
+                }
+            }
         }
     }
 
@@ -267,12 +271,12 @@ impl MCTSContext {
         let ret = self.search_leaf(start,modifier);
         match ret {
             (path,SearchResult::Expand(leaf)) => {
-                let (nn_policy,nn_value) = self.predict_queue.async_predict(self.graph_filename.clone(), leaf.clone()).await;
+                let (nn_policy,nn_value,nn_distribution) = self.predict_queue.async_predict(self.graph_filename.clone(), leaf.clone()).await;
                 self.expand(leaf,nn_policy);
-                self.add_value(&path,nn_value);
+                self.add_value(&path,nn_value,nn_distribution.as_ref());
             },
             (path,SearchResult::Reward(reward)) => {
-                self.add_value(&path,reward);
+                self.add_value(&path,reward,None);
             },
         }
     }
@@ -290,7 +294,7 @@ impl MCTSContext {
         self.remove_unused_nodes(s);
 
         if !self.nodes.contains_key( s ) {
-            let (nn_policy,_) = self.predict_queue.async_predict(self.graph_filename.clone(), s.clone()).await;
+            let (nn_policy,_,_) = self.predict_queue.async_predict(self.graph_filename.clone(), s.clone()).await;
             self.expand( s.clone(), nn_policy );
         }
 
@@ -306,6 +310,69 @@ impl MCTSContext {
         get_mcts_policy( &self.nodes.get(s).unwrap().N )
     }
 
+    // 探索済みの子状態のうち、最も訪問回数の多いものを辿ります
+    fn most_visited_child(&self, s:&State, modifier:&mut Modifier, a:&Action) -> Option<State> {
+        s.outcomes(modifier, a).into_iter()
+            .filter_map(|(ns,_)| self.nodes.get(&ns).map(|node| (node.N.iter().sum::<f32>(), ns)))
+            .fold(None, |best:Option<(f32,State)>, (n,ns)| match best {
+                Some((m,_)) if m >= n => best,
+                _ => Some((n,ns)),
+            })
+            .map(|(_,ns)| ns)
+    }
+
+    // 行動aの後の読み筋です。子状態は最も訪問回数の多いものを、行動は最も訪問回数の多いものを選びます
+    fn principal_variation(&self, s:&State, modifier:&mut Modifier, a:&Action) -> Vec<Action> {
+        let mut pv = vec![];
+        let mut s = match self.most_visited_child(s, modifier, a) {
+            Some(x) => x,
+            None => return pv,
+        };
+
+        while !s.is_terminated() {
+            let node = match self.nodes.get(&s) {
+                Some(x) => x,
+                None => break,
+            };
+            let (best,n) = node.N.iter().enumerate().fold((0,0.0), |(i,m),(j,n)| if *n > m { (j,*n) } else { (i,m) });
+            if n == 0.0 {
+                break
+            }
+
+            let action = Action::from_usize(best).unwrap();
+            pv.push(action);
+            s = match self.most_visited_child(&s, modifier, &action) {
+                Some(x) => x,
+                None => break,
+            };
+        }
+        pv
+    }
+
+    // 探索後の根の統計です。合法手ごとに訪問回数・行動価値・事前確率・読み筋を返します
+    pub fn analyze(&self, s:&State, modifier:&mut Modifier) -> Vec<ActionAnalysis> {
+        let node = match self.nodes.get(s) {
+            Some(x) => x,
+            None => return vec![],
+        };
+
+        let mut result = vec![];
+        for a in 0..ACTION_NUM {
+            let action = Action::from_usize(a).unwrap();
+            if s.check_action_ex(&modifier.mod_param, &action) {
+                result.push(ActionAnalysis {
+                    action: action,
+                    n: node.N[a],
+                    q: self.risk_mode.action_value(node.W[a], node.N[a], node.H.get(a)),
+                    p: node.P[a],
+                    pv: self.principal_variation(s, modifier, &action),
+                });
+            }
+        }
+        result.sort_by(|x,y| y.n.partial_cmp(&x.n).unwrap());
+        result
+    }
+
     // デバッグする時に呼び出すコードなので無効にしておきます
     #[allow(dead_code)]
     pub fn print_stats(&self) {
@@ -319,3 +386,49 @@ pub fn print_mcts_stats() {
     eprintln!("State size:{}", std::mem::size_of::<State>());
     eprintln!("Node size:{}", std::mem::size_of::<Node>());
 }
+
+#[cfg(test)]
+fn search_with_uniform_policy(risk_mode:RiskMode, s:&State, modifier:&mut Modifier, num_simulations:u32) -> (MCTSContext,ActionVector) {
+    use std::future::Future;
+    use std::task::{Context,Poll};
+    use noop_waker::noop_waker;
+
+    let mut predictor = Predictor::new();
+    let mut context = MCTSContext::new(1.0, 0.15, 0.0, risk_mode, predictor.get_queue(), "test".to_string());
+    let waker = noop_waker();
+    let mut ctx = Context::from_waker(&waker);
+
+    let policy = {
+        let mut future = Box::pin(context.search(s, modifier, num_simulations));
+        loop {
+            if let Poll::Ready(x) = future.as_mut().poll(&mut ctx) {
+                break x
+            }
+            predictor.predict_batch_with(|_| ([1.0 / ACTION_NUM as f32; ACTION_NUM], 0.0));
+        }
+    };
+    (context, policy)
+}
+
+#[test]
+fn test_threshold_search()
+{
+    use super::seed::new_rng;
+
+    // 品質が閾値に1足りない状態で、作業すれば完成します。1ターン目は手が限られるので2ターン目にします
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let s = State { turn:2, working:mod_param.max_working - 1, quality:mod_param.bonus_threshold - 1, .. State::new(&mod_param) };
+    let q = |analysis:&Vec<ActionAnalysis>, a:Action| analysis.iter().find(|x| x.action == a).unwrap().q;
+
+    // 閾値モードでは、閾値に届かずに完成させる手の価値は0で、加工と作業を同時にする方を選びます
+    let mut modifier = Modifier { mod_param:mod_param.clone(), rng:new_rng(1), forced_success:None };
+    let (context, policy) = search_with_uniform_policy(RiskMode::Threshold, &s, &mut modifier, 200);
+    let analysis = context.analyze(&s, &mut modifier);
+    assert_eq!( 0.0, q(&analysis, Action::BasicSynthesis) );
+    assert_eq!( 1.0, q(&analysis, Action::DelicateSynthesis) );
+    assert_eq!( vec![Action::DelicateSynthesis as usize], select_max_indices(&policy) );
+
+    // 期待値モードなら、閾値に届かなくても品質の分の報酬があります
+    let completed = s.run_action(&mut modifier, &Action::BasicSynthesis);
+    assert!( RiskMode::Mean.terminal_value(&completed, &mod_param) > 0.0 );
+}
@@ -1,19 +1,45 @@
 use std::error::Error;
 
+use serde::{Serialize,Deserialize};
 use tch::*;
 use tch::nn::*;
 
 use super::logic::{State,ACTION_NUM};
 use super::setting::ModifierParameter;
-use super::encoding::{encode_state_batch,decode_pv_batch};
+use super::encoding::{encode_state_batch,decode_pv_batch,VALUE_ATOMS};
 use super::mcts::*;
 
-pub const STATE_NUM : usize = 36;
+pub use super::encoding::STATE_NUM;
 
-#[derive(Debug,Copy,Clone,PartialEq)]
+// バリューヘッドの種類です
+#[derive(Debug,Copy,Clone,PartialEq,Serialize,Deserialize)]
+pub enum ValueHead {
+    Scalar,       // 報酬の期待値をsigmoidで1つ出力します
+    Categorical,  // 報酬[0,1]をVALUE_ATOMS点に区切った分布をsoftmaxで出力します
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Serialize,Deserialize)]
 pub enum NetworkType {
-    FullyConnected(usize,usize),
-    Residual(usize,usize),
+    FullyConnected(usize,usize,ValueHead),
+    Residual(usize,usize,ValueHead),
+}
+
+impl ValueHead {
+    // 末尾に-distが付いていれば分布を出力します
+    fn parse(xs:&[&str]) -> Result<Self, String> {
+        match xs.first() {
+            None => Ok(ValueHead::Scalar),
+            Some(&"dist") => Ok(ValueHead::Categorical),
+            Some(_) => Err("can't parse value head".to_string()),
+        }
+    }
+
+    fn suffix(&self) -> &str {
+        match *self {
+            ValueHead::Scalar => "",
+            ValueHead::Categorical => "-dist",
+        }
+    }
 }
 
 impl NetworkType {
@@ -33,7 +59,7 @@ impl NetworkType {
             Err(_) => Err("can't parse hidden_nodes".to_string()),
         }?;
 
-        Ok(NetworkType::FullyConnected(depth, hidden_nodes))
+        Ok(NetworkType::FullyConnected(depth, hidden_nodes, ValueHead::parse(&xs[2..])?))
     }
 
     fn parse_residual(xs:&[&str]) -> Result<Self, String> {
@@ -51,7 +77,7 @@ impl NetworkType {
             Err(_) => Err("can't parse hidden_nodes".to_string()),
         }?;
 
-        Ok(NetworkType::Residual(depth, hidden_nodes))
+        Ok(NetworkType::Residual(depth, hidden_nodes, ValueHead::parse(&xs[2..])?))
     }
 
     pub fn from_name(name: &str) -> Result<Self, String> {
@@ -69,8 +95,8 @@ impl NetworkType {
 
     pub fn to_string(&self) -> String {
         match *self {
-            NetworkType::FullyConnected(depth,hidden_nodes) => format!("fc-{}-{}", depth, hidden_nodes),
-            NetworkType::Residual(depth,hidden_nodes) => format!("residual-{}-{}", depth, hidden_nodes),
+            NetworkType::FullyConnected(depth,hidden_nodes,value_head) => format!("fc-{}-{}{}", depth, hidden_nodes, value_head.suffix()),
+            NetworkType::Residual(depth,hidden_nodes,value_head) => format!("residual-{}-{}{}", depth, hidden_nodes, value_head.suffix()),
         }
     }
 }
@@ -83,8 +109,8 @@ impl argh::FromArgValue for NetworkType {
 
 pub fn create_network(vs: &nn::Path, network_type: NetworkType) -> Box<dyn DualNetwork> {
     match network_type {
-        NetworkType::FullyConnected(depth, hidden_nodes) => Box::new(FullyConnectedNetwork::new(vs, depth, hidden_nodes)),
-        NetworkType::Residual(depth, hidden_nodes) => Box::new(ResidualNetwork::new(vs, depth, hidden_nodes)),
+        NetworkType::FullyConnected(depth, hidden_nodes, value_head) => Box::new(FullyConnectedNetwork::new(vs, depth, hidden_nodes, value_head)),
+        NetworkType::Residual(depth, hidden_nodes, value_head) => Box::new(ResidualNetwork::new(vs, depth, hidden_nodes, value_head)),
     }
 }
 
@@ -92,7 +118,7 @@ pub trait DualNetwork {
 
     fn forward_t(&self, input: &Tensor, train:bool) -> (Tensor,Tensor);
 
-    fn predict_batch(&self, states:&[State], mod_param:&ModifierParameter) -> Result<Vec<(ActionVector,f32)>, Box<dyn Error>> {
+    fn predict_batch(&self, states:&[State], mod_param:&ModifierParameter) -> Result<Vec<Prediction>, Box<dyn Error>> {
         let state_vec_t = encode_state_batch( states, mod_param );
         let pv_t = self.forward_t(&state_vec_t, false);
         Ok(decode_pv_batch(pv_t))
@@ -131,18 +157,23 @@ fn create_policy_network(vs: &nn::Path, hidden_nodes: usize) -> SequentialT {
         .add_fn(|xs| xs.softmax(1,Kind::Float))
 }
 
-fn create_value_network(vs: &nn::Path, hidden_nodes: usize) -> SequentialT {
-    nn::seq_t()
-        .add(nn::linear( vs / "value", hidden_nodes as i64, 1, Default::default()))
-        .add_fn(|xs| xs.sigmoid())
+fn create_value_network(vs: &nn::Path, hidden_nodes: usize, value_head: ValueHead) -> SequentialT {
+    match value_head {
+        ValueHead::Scalar => nn::seq_t()
+            .add(nn::linear( vs / "value", hidden_nodes as i64, 1, Default::default()))
+            .add_fn(|xs| xs.sigmoid()),
+        ValueHead::Categorical => nn::seq_t()
+            .add(nn::linear( vs / "value", hidden_nodes as i64, VALUE_ATOMS as i64, Default::default()))
+            .add_fn(|xs| xs.softmax(1,Kind::Float)),
+    }
 }
 
 impl FullyConnectedNetwork {
-    pub fn new(vs: &nn::Path, depth: usize, hidden_nodes: usize) -> FullyConnectedNetwork {
+    pub fn new(vs: &nn::Path, depth: usize, hidden_nodes: usize, value_head: ValueHead) -> FullyConnectedNetwork {
         FullyConnectedNetwork {
             main_net: create_main_network(vs, depth, hidden_nodes),
             policy_net: create_policy_network(vs, hidden_nodes),
-            value_net: create_value_network(vs, hidden_nodes),
+            value_net: create_value_network(vs, hidden_nodes, value_head),
         }
     }
 }
@@ -188,11 +219,11 @@ impl ResidualUnit {
 }
 
 impl ResidualNetwork {
-    pub fn new(vs: &nn::Path, depth: usize, hidden_nodes: usize) -> ResidualNetwork {
+    pub fn new(vs: &nn::Path, depth: usize, hidden_nodes: usize, value_head: ValueHead) -> ResidualNetwork {
         let input_net = nn::linear( vs / "input", STATE_NUM as i64, hidden_nodes as i64, Default::default());
         let residual_units = (0..depth).into_iter().map(|i| ResidualUnit::new(&(vs/format!("residual_units_{}",i)),hidden_nodes)).collect();
         let policy_net = create_policy_network(vs, hidden_nodes);
-        let value_net = create_value_network(vs, hidden_nodes);
+        let value_net = create_value_network(vs, hidden_nodes, value_head);
 
         ResidualNetwork { input_net, residual_units, policy_net, value_net }
     }
@@ -208,3 +239,11 @@ impl DualNetwork for ResidualNetwork {
         (policy_output, value_output)
     }
 }
+
+#[test]
+fn test_network_type() {
+    assert_eq!(NetworkType::from_name("fc-4-128"), Ok(NetworkType::FullyConnected(4,128,ValueHead::Scalar)));
+    assert_eq!(NetworkType::from_name("residual-8-256-dist"), Ok(NetworkType::Residual(8,256,ValueHead::Categorical)));
+    assert!(NetworkType::from_name("fc-4-128-foo").is_err());
+    assert_eq!(NetworkType::Residual(8,256,ValueHead::Categorical).to_string(), "residual-8-256-dist");
+}
@@ -5,7 +5,7 @@ use std::task::{Context,Poll};
 use std::cell::{Cell,RefCell};
 use std::rc::Rc;
 
-use super::mcts::ActionVector;
+use super::mcts::{ActionVector,Prediction};
 use super::logic::State;
 use super::setting::ModifierParameter;
 use super::network::*;
@@ -15,7 +15,7 @@ use super::network::*;
 // そのままFutureの戻り値として使えます。
 #[derive(Clone)]
 pub struct PredictResult {
-    res : Rc<Cell<Poll<(ActionVector,f32)>>>
+    res : Rc<Cell<Poll<Prediction>>>
 }
 
 impl PredictResult {
@@ -25,9 +25,9 @@ impl PredictResult {
 }
 
 impl Future for PredictResult {
-    type Output = (ActionVector,f32);
+    type Output = Prediction;
 
-    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<(ActionVector,f32)> {
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Prediction> {
         ctx.waker().wake_by_ref();
         self.res.get()
     }
@@ -76,13 +76,28 @@ impl Predictor {
         tasks.clear();
     }
 
+    // ネットワークを使わずに関数で予測します。解析でネットワーク以外の評価器を使う時に使います
+    // 評価値の分布は予測しないので、ヒストグラムには評価値そのものを足します
+    pub fn predict_batch_with<F:FnMut(&State) -> (ActionVector,f32)>(&mut self, mut f:F) {
+        let mut tasks = self.tasks.borrow_mut();
+
+        for (_,task_vec) in tasks.iter() {
+            for (s,result) in task_vec {
+                let (policy,value) = f(s);
+                result.res.set(Poll::Ready((policy,value,None)))
+            }
+        }
+
+        tasks.clear();
+    }
+
     pub fn get_queue(&self) -> PredictQueue {
         PredictQueue { tasks : self.tasks.clone() }
     }
 }
 
 impl PredictQueue {
-    pub async fn async_predict( &self, name:String, x:State ) -> (ActionVector,f32) {
+    pub async fn async_predict( &self, name:String, x:State ) -> Prediction {
         let pr = PredictResult::new();
         self.tasks.borrow_mut().entry(name).or_insert(Vec::new()).push( (x,pr.clone()) );
         pr.await
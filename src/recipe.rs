@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use serde::{Serialize,Deserialize};
+
+use super::logic::{Condition,CONDITIONS};
+use super::setting::{ModifierParameter,CrafterStatus,RecipeStatus};
+use super::condition::ConditionModel;
+use super::timing::TimingModel;
+use super::target::{Collectable,Scoring};
+
+// レシピの定義です。
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct Recipe
+{
+    pub id : u32,
+    pub name : String,
+    pub recipe_level : u32,               // レシピレベル(表示用)
+
+    #[serde(flatten)]
+    pub status : RecipeStatus,
+
+    #[serde(default)]
+    pub expert : bool,                    // 高難度レシピかどうか
+
+    #[serde(default)]
+    pub conditions : Vec<Condition>,      // 高難度レシピで出現する状態
+
+    #[serde(default)]
+    pub timing : Option<TimingModel>,     // イベントごとの所要時間と時間報酬(JSONのみ)
+
+    #[serde(default)]
+    pub collectable : Option<Collectable>, // 収集品の段階(JSONのみ)
+
+    #[serde(default)]
+    pub scoring : Option<Scoring>,        // 納品イベントのスコア表(JSONのみ)
+}
+
+// ローカルファイルから読み込んだレシピの一覧です。
+pub struct RecipeCatalog
+{
+    recipes : Vec<Recipe>,
+}
+
+impl Recipe {
+
+    pub fn condition_model(&self) -> ConditionModel {
+        if self.expert {
+            ConditionModel::new_expert_recipe(&self.conditions)
+        }
+        else {
+            ConditionModel::new_normal_recipe()
+        }
+    }
+
+    pub fn create_mod_param(&self, crafter:&CrafterStatus) -> ModifierParameter {
+        let mut mod_param = ModifierParameter::new_from_status(crafter, &self.status);
+        mod_param.condition_model = self.condition_model();
+        if let Some(x) = &self.timing {
+            mod_param.timing = x.clone();
+        }
+        mod_param.collectable = self.collectable.clone();
+        mod_param.scoring = self.scoring.clone();
+        mod_param
+    }
+}
+
+fn parse_condition(name:&str) -> Result<Condition, String> {
+    CONDITIONS.iter()
+        .find(|c| format!("{:?}", c) == name)
+        .copied()
+        .ok_or_else(|| format!("unknown condition {}", name))
+}
+
+fn parse_csv(text:&str) -> Result<Vec<Recipe>, String> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let header : Vec<&str> = match lines.next() {
+        Some(x) => x.split(',').map(|x| x.trim()).collect(),
+        None => return Ok(Vec::new()),
+    };
+
+    let mut recipes = Vec::new();
+    for line in lines {
+        let row : HashMap<&str,&str> = header.iter().cloned().zip(line.split(',').map(|x| x.trim())).collect();
+
+        let get = |key:&str| -> Result<&str, String> {
+            row.get(key).cloned().ok_or_else(|| format!("missing column {} in \"{}\"", key, line))
+        };
+        let get_u32 = |key:&str| -> Result<u32, String> {
+            get(key)?.parse::<u32>().map_err(|_| format!("can't parse {} in \"{}\"", key, line))
+        };
+
+        // 状態は'|'区切りで並べます
+        let conditions = match row.get("conditions") {
+            Some(x) if !x.is_empty() => x.split('|').map(|c| parse_condition(c.trim())).collect::<Result<Vec<_>,_>>()?,
+            _ => Vec::new(),
+        };
+
+        recipes.push(Recipe {
+            id : get_u32("id")?,
+            name : get("name")?.to_string(),
+            recipe_level : get_u32("recipe_level")?,
+            status : RecipeStatus {
+                class_job_level : get_u32("class_job_level")?,
+                difficulty : get_u32("difficulty")?,
+                quality : get_u32("quality")?,
+                durability : get_u32("durability")?,
+                progress_divider : get_u32("progress_divider")?,
+                quality_divider : get_u32("quality_divider")?,
+                progress_modifier : get_u32("progress_modifier")?,
+                quality_modifier : get_u32("quality_modifier")?,
+            },
+            expert : matches!(row.get("expert"), Some(&"true") | Some(&"1")),
+            conditions,
+            timing : None,
+            collectable : None,
+            scoring : None,
+        });
+    }
+
+    Ok(recipes)
+}
+
+impl RecipeCatalog {
+
+    // 拡張子が.csvならCSV、それ以外はJSONとして読み込みます
+    pub fn load(path:&str) -> Result<RecipeCatalog, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+
+        let recipes = if path.ends_with(".csv") {
+            parse_csv(&text)?
+        }
+        else {
+            serde_json::from_str(&text).map_err(|e| format!("can't parse {}: {}", path, e))?
+        };
+
+        Ok(RecipeCatalog { recipes })
+    }
+
+    // IDか名前で検索します。数値として読めればIDを優先します
+    pub fn find(&self, key:&str) -> Option<&Recipe> {
+        if let Ok(id) = key.parse::<u32>() {
+            if let Some(x) = self.recipes.iter().find(|r| r.id == id) {
+                return Some(x)
+            }
+        }
+        self.recipes.iter().find(|r| r.name == key)
+    }
+}
+
+#[test]
+fn test_parse_csv()
+{
+    let text = "id,name,recipe_level,class_job_level,difficulty,quality,durability,progress_divider,quality_divider,progress_modifier,quality_modifier,expert,conditions\n\
+                1,Test Recipe,560,90,3500,7200,80,130,115,90,80,true,HighQuality|Solid\n";
+    let catalog = RecipeCatalog { recipes:parse_csv(text).unwrap() };
+
+    let recipe = catalog.find("Test Recipe").unwrap();
+    assert_eq!( 1, recipe.id );
+    assert_eq!( 3500, recipe.status.difficulty );
+    assert_eq!( vec![Condition::HighQuality, Condition::Solid], recipe.conditions );
+    assert!( catalog.find("1").is_some() );
+    assert!( catalog.find("2").is_none() );
+}
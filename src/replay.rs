@@ -7,6 +7,15 @@ use bzip2::read::BzDecoder;
 use super::logic::*;
 use super::selfplay::*;
 use super::gcs::*;
+use super::cache::WeightsCache;
+use super::setting::ModifierParameter;
+use super::target::{Collectable,hq_percent};
+
+pub struct ReplayParameter {
+    pub record_names : Vec<String>,
+    pub mod_param : ModifierParameter,    // 成果の集計に使うレシピ設定
+    pub episode_seed : Option<u64>,       // 指定した時はこのシードのエピソードを再実行します
+}
 
 fn get_records( record_name: String ) -> Vec<Record> {
     eprintln!("{} Downloading...", record_name);
@@ -25,7 +34,7 @@ fn get_records( record_name: String ) -> Vec<Record> {
     reader.read_to_end(&mut serialized).unwrap();
 
     // デシリアライズ
-    bincode::deserialize(&serialized).unwrap()
+    decode_records(&serialized).unwrap()
 }
 
 const HEADER: [&str; 16] = [
@@ -50,7 +59,7 @@ const HEADER: [&str; 16] = [
 fn format_state( s:&State ) -> String {
     format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
         s.turn,
-        s.time,
+        s.time as f32 / 1000.0,
         s.working,
         s.quality,
         s.durability,
@@ -69,6 +78,7 @@ fn format_state( s:&State ) -> String {
 }
 
 fn write_record( record: &Record ) {
+    println!("seed: {}", record.seed);
     println!("{}", HEADER.join("\t").to_string());
 
     for sample in &record.samples {
@@ -76,6 +86,28 @@ fn write_record( record: &Record ) {
     }
 }
 
+// 最終状態の成果を表示します
+fn write_result( record: &Record, mod_param: &ModifierParameter ) {
+    let s = &record.last_state;
+    if !s.is_completed() {
+        println!("結果\t失敗\t報酬\t{}", record.reward);
+        return
+    }
+
+    let mut columns = vec![
+        format!("品質\t{}", s.quality),
+        format!("HQ\t{}%", hq_percent(s.quality, mod_param.max_quality)),
+    ];
+    if let Some(x) = &mod_param.collectable {
+        columns.push(format!("収集価値\t{}\tスクリップ\t{}", Collectable::collectability(s.quality), x.scrip(s.quality)));
+    }
+    if let Some(x) = &mod_param.scoring {
+        columns.push(format!("{}\t{}", x.name, x.score(s.quality)));
+    }
+    columns.push(format!("報酬\t{}", record.reward));
+    println!("結果\t{}", columns.join("\t"));
+}
+
 fn count_skill_histogram( counter: &mut HashMap<(Action,Condition),u32>, record: &Record ) {
     for sample in &record.samples {
         let key = (sample.action, sample.state.condition);
@@ -85,43 +117,79 @@ fn count_skill_histogram( counter: &mut HashMap<(Action,Condition),u32>, record:
 
 fn write_skill_histogram( counter: &HashMap<(Action,Condition),u32> ) {
 
-    println!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-        "アクション",
-        Condition::Standard.translate_ja(),
-        Condition::HighQuality.translate_ja(),
-        Condition::HighProgress.translate_ja(),
-        Condition::HighEfficiency.translate_ja(),
-        Condition::HighSustain.translate_ja(),
-        Condition::Solid.translate_ja(),
-        Condition::Stable.translate_ja());
+    let header : Vec<&str> = CONDITIONS.iter().map(|c| c.translate_ja()).collect();
+    println!("アクション\t{}", header.join("\t"));
 
     for a in 0..ACTION_NUM {
         let action = Action::from_u64(a as u64).unwrap();
+        let counts : Vec<String> = CONDITIONS.iter().map(|c| counter.get(&(action,*c)).unwrap_or(&0).to_string()).collect();
 
-        println!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-            action.translate_ja(),
-            counter.get(&(action,Condition::Standard)).unwrap_or(&0),
-            counter.get(&(action,Condition::HighQuality)).unwrap_or(&0),
-            counter.get(&(action,Condition::HighProgress)).unwrap_or(&0),
-            counter.get(&(action,Condition::HighEfficiency)).unwrap_or(&0),
-            counter.get(&(action,Condition::HighSustain)).unwrap_or(&0),
-            counter.get(&(action,Condition::Solid)).unwrap_or(&0),
-            counter.get(&(action,Condition::Stable)).unwrap_or(&0));
+        println!("{}\t{}", action.translate_ja(), counts.join("\t"));
     }
 }
 
-pub fn run_replay( record_names:Vec<String> ) {
+// 記録されたモデルと設定で、シードからエピソードを再実行して記録と比べます
+fn rerun_episode( record: &Record, mod_param: &ModifierParameter ) {
+    let mut cache = WeightsCache::new();
+    let graph = cache.load_weights(&record.name, record.network_type).unwrap();
+
+    let episode_param = EpisodeParameter {
+        mod_param: mod_param.clone(),
+        initial_state: record.samples.first().map(|x| x.state.clone()).unwrap_or_else(|| record.last_state.clone()),
+        mcts_simulation_num: record.mcts_simulation_num,
+        alpha: record.alpha,
+        eps: record.eps,
+        start_greedy_turn: record.start_greedy_turn,
+        seed: record.seed,
+        risk_mode: record.risk_mode,
+    };
+    let rerun = run_episode(&episode_param, record.seed, &record.name, &graph);
+
+    write_record( &rerun );
+    write_result( &rerun, mod_param );
+
+    let actions : Vec<Action> = record.samples.iter().map(|x| x.action).collect();
+    let rerun_actions : Vec<Action> = rerun.samples.iter().map(|x| x.action).collect();
+    let reproduced = actions == rerun_actions && record.reward == rerun.reward;
+    println!("再現\t{}", if reproduced { "一致" } else { "不一致" });
+}
+
+pub fn run_replay( param:ReplayParameter ) {
+
+    if let Some(seed) = param.episode_seed {
+        for record_name in param.record_names {
+            for record in get_records(record_name) {
+                if record.seed == seed {
+                    rerun_episode( &record, &param.mod_param );
+                    return
+                }
+            }
+        }
+        panic!("episode seed {} is not found", seed);
+    }
 
     let mut counter : HashMap<(Action,Condition),u32> = HashMap::new();
+    let mut scores = vec![];
 
-    for record_name in record_names {
+    for record_name in param.record_names {
         let records = get_records(record_name);
 
         for record in records {
             write_record( &record );
+            write_result( &record, &param.mod_param );
             count_skill_histogram( &mut counter, &record );
+
+            if let Some(x) = &param.mod_param.scoring {
+                scores.push(if record.last_state.is_completed() { x.score(record.last_state.quality) } else { 0 });
+            }
         }
     }
 
     write_skill_histogram( &counter );
+
+    if let Some(x) = &param.mod_param.scoring {
+        if !scores.is_empty() {
+            println!("{}(平均)\t{:.1}", x.name, scores.iter().sum::<u32>() as f32 / scores.len() as f32);
+        }
+    }
 }
@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use super::logic::State;
+use super::setting::ModifierParameter;
+use super::target::hq_percent;
+
+// 終局状態の報酬モデルです。
+// MCTSの終端評価・Recordの報酬・シミュレーションの集計は全てここを通します。
+// バリューネットワークの出力がsigmoidで、MCTSのヒストグラムも[0,1]を区切るので、報酬は[0,1]に収めます
+pub trait RewardModel
+{
+    // 完成した(または中断した)状態の報酬です。失敗時はget_rewardが0にします
+    // 範囲外の値を返すとget_rewardで落ちます
+    fn reward(&self, s:&State, mod_param:&ModifierParameter) -> f32;
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum RewardModelType {
+    Blend,        // 品質・時間・閾値の重みづけ
+    Hq,           // HQ率
+    Collectable,  // 収集価値の段階ごとの報酬
+    Scoring,      // 納品イベントのスコア表
+    Completion,   // 完成確率
+    Throughput,   // 実時間1秒あたりの期待ポイント
+}
+
+impl RewardModelType {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "blend" => Ok(RewardModelType::Blend),
+            "hq" => Ok(RewardModelType::Hq),
+            "collectable" => Ok(RewardModelType::Collectable),
+            "scoring" => Ok(RewardModelType::Scoring),
+            "completion" => Ok(RewardModelType::Completion),
+            "throughput" => Ok(RewardModelType::Throughput),
+            _ => Err("unknown reward model".to_string()),
+        }
+    }
+}
+
+impl argh::FromArgValue for RewardModelType {
+    fn from_arg_value(value: &str) -> Result<Self, String> {
+        RewardModelType::from_name(value)
+    }
+}
+
+pub fn create_reward_model(reward_model_type: RewardModelType) -> Arc<dyn RewardModel + Sync + Send> {
+    match reward_model_type {
+        RewardModelType::Blend => Arc::new(BlendReward {}),
+        RewardModelType::Hq => Arc::new(HqReward {}),
+        RewardModelType::Collectable => Arc::new(CollectableReward {}),
+        RewardModelType::Scoring => Arc::new(ScoringReward {}),
+        RewardModelType::Completion => Arc::new(CompletionReward {}),
+        RewardModelType::Throughput => Arc::new(ThroughputReward {}),
+    }
+}
+
+// 報酬関数です。
+pub fn get_reward(s:&State, mod_param:&ModifierParameter) -> f32 {
+    if s.is_destroyed() {
+        0.0
+    }
+    else {
+        let reward = mod_param.reward_model.reward(s, mod_param);
+        assert!((0.0..=1.0).contains(&reward), "reward {} is out of [0,1]", reward);
+        reward
+    }
+}
+
+// 品質・時間・閾値を重みづけした報酬です。
+#[derive(Debug,Clone)]
+pub struct BlendReward {}
+
+// HQ率を報酬にします。
+#[derive(Debug,Clone)]
+pub struct HqReward {}
+
+// 到達した収集価値の段階のスクリップを、最高段階を1として報酬にします。
+#[derive(Debug,Clone)]
+pub struct CollectableReward {}
+
+// スコア表の点数を、品質上限での点数を1として報酬にします。
+#[derive(Debug,Clone)]
+pub struct ScoringReward {}
+
+// 完成したかどうかだけを報酬にします。期待値は完成確率になります
+#[derive(Debug,Clone)]
+pub struct CompletionReward {}
+
+// 1回の製作で得られるポイントを所要時間で割った効率を報酬にします。
+// 最高のポイントを最短時間で得た時の効率を1とします
+#[derive(Debug,Clone)]
+pub struct ThroughputReward {}
+
+impl RewardModel for BlendReward {
+    fn reward(&self, s:&State, mod_param:&ModifierParameter) -> f32 {
+        // 品質[0,1]
+        let quality_reward = s.quality as f32 / mod_param.max_quality as f32;
+
+        // ターン(タイム)ボーナス[0,1]
+        let time_reward = mod_param.timing.time_reward(s.time);
+
+        // max品質ボーナス
+        let threshold_reward = if s.quality >= mod_param.bonus_threshold { 1.0 } else { 0.0 };
+
+        // 品質ボーナス割合は計算で求めます
+        let quality_t = 1.0 - mod_param.bonus_time_t - mod_param.bonus_threshold_t;
+
+        // 結果は重みづけでマージ。重みの合計が1を超える設定でも[0,1]に収めます
+        (quality_reward*quality_t + time_reward*mod_param.bonus_time_t + threshold_reward*mod_param.bonus_threshold_t).max(0.0).min(1.0)
+    }
+}
+
+impl RewardModel for HqReward {
+    fn reward(&self, s:&State, mod_param:&ModifierParameter) -> f32 {
+        hq_percent(s.quality, mod_param.max_quality) as f32 / 100.0
+    }
+}
+
+impl RewardModel for CollectableReward {
+    fn reward(&self, s:&State, mod_param:&ModifierParameter) -> f32 {
+        mod_param.collectable.as_ref().map_or(0.0, |x| x.reward(s.quality))
+    }
+}
+
+impl RewardModel for ScoringReward {
+    fn reward(&self, s:&State, mod_param:&ModifierParameter) -> f32 {
+        match &mod_param.scoring {
+            Some(x) => {
+                let max_score = x.score(mod_param.max_quality);
+                // 品質上限より手前で点数が最大になるスコア表もあるので、1で抑えます
+                if max_score == 0 { 0.0 } else { (x.score(s.quality) as f32 / max_score as f32).min(1.0) }
+            },
+            None => 0.0,
+        }
+    }
+}
+
+impl RewardModel for CompletionReward {
+    fn reward(&self, s:&State, _mod_param:&ModifierParameter) -> f32 {
+        if s.is_completed() { 1.0 } else { 0.0 }
+    }
+}
+
+impl ThroughputReward {
+
+    // ポイントとその最大値です。スコア表があればその点数、収集品ならスクリップ、それ以外はHQ率で数えます
+    fn points(s:&State, mod_param:&ModifierParameter) -> (f32,f32) {
+        if let Some(x) = &mod_param.scoring {
+            return (x.score(s.quality) as f32, x.score(mod_param.max_quality) as f32)
+        }
+        match &mod_param.collectable {
+            Some(x) => (x.scrip(s.quality) as f32, x.scrip(mod_param.max_quality) as f32),
+            None => (hq_percent(s.quality, mod_param.max_quality) as f32, 100.0),
+        }
+    }
+}
+
+impl RewardModel for ThroughputReward {
+    fn reward(&self, s:&State, mod_param:&ModifierParameter) -> f32 {
+        if !s.is_completed() || s.time == 0 {
+            return 0.0
+        }
+
+        let (points, max_points) = ThroughputReward::points(s, mod_param);
+        let best_time = mod_param.timing.best_time;
+        if max_points <= 0.0 || best_time <= 0.0 {
+            return 0.0
+        }
+
+        let rate = points / (s.time as f32 / 1000.0);
+        let max_rate = max_points / best_time;
+        (rate / max_rate).max(0.0).min(1.0)
+    }
+}
+
+#[test]
+fn test_reward_model() {
+    let mut mod_param = ModifierParameter::new_fountain_of_usouso();
+    let mut s = State::new(&mod_param);
+    s.completed = true;
+    s.quality = mod_param.max_quality;
+    s.time = 50000;
+
+    mod_param.reward_model = create_reward_model(RewardModelType::Completion);
+    assert_eq!(get_reward(&s, &mod_param), 1.0);
+
+    mod_param.reward_model = create_reward_model(RewardModelType::Hq);
+    assert_eq!(get_reward(&s, &mod_param), 1.0);
+
+    mod_param.reward_model = create_reward_model(RewardModelType::Throughput);
+    assert_eq!(get_reward(&s, &mod_param), 1.0);
+    s.time = 100000;
+    assert_eq!(get_reward(&s, &mod_param), 0.5);
+
+    s.completed = false;
+    s.durability = 0;
+    assert_eq!(get_reward(&s, &mod_param), 0.0);
+}
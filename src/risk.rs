@@ -0,0 +1,156 @@
+use serde::{Serialize,Deserialize};
+
+use super::logic::State;
+use super::setting::ModifierParameter;
+use super::reward::get_reward;
+
+// 評価値のヒストグラムの分割数です。評価値はRewardModelの約束通り[0,1]に収まります
+pub const RISK_BINS: usize = 20;
+
+pub type ValueHistogram = [f32;RISK_BINS];
+
+// MCTSで最大化する指標です。
+#[derive(Debug,Copy,Clone,PartialEq,Serialize,Deserialize)]
+pub enum RiskMode {
+    Mean,             // 報酬の期待値(従来通り)
+    Threshold,        // 品質がbonus_threshold以上で完成する確率
+    Quantile(f32),    // 報酬の下側alpha分位点
+    CVaR(f32),        // 報酬の下側alphaの条件付き期待値
+}
+
+impl RiskMode {
+
+    fn parse_alpha(xs:&[&str]) -> Result<f32, String> {
+        if xs.len() < 1 {
+            return Err("can't parse alpha".to_string())
+        }
+
+        match xs[0].parse::<f32>() {
+            Ok(x) if x > 0.0 && x <= 1.0 => Ok(x),
+            _ => Err("alpha must be in (0,1]".to_string()),
+        }
+    }
+
+    // mean, threshold, quantile-0.1, cvar-0.1 の形式です
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        let xs : Vec<&str> = name.split('-').collect();
+
+        match xs[0] {
+            "mean" => Ok(RiskMode::Mean),
+            "threshold" => Ok(RiskMode::Threshold),
+            "quantile" => Ok(RiskMode::Quantile(RiskMode::parse_alpha(&xs[1..])?)),
+            "cvar" => Ok(RiskMode::CVaR(RiskMode::parse_alpha(&xs[1..])?)),
+            _ => Err("unknown risk mode".to_string()),
+        }
+    }
+
+    // 子ノードの評価値の分布を保持する必要があるかどうか
+    pub fn needs_histogram(&self) -> bool {
+        match *self {
+            RiskMode::Quantile(_) | RiskMode::CVaR(_) => true,
+            _ => false,
+        }
+    }
+
+    // 終局状態の評価値です。閾値モードでは達成したかどうかだけを返します
+    // Recordの報酬もこれを使い、バリューネットワークが同じ指標を学習するようにします
+    pub fn terminal_value(&self, s:&State, mod_param:&ModifierParameter) -> f32 {
+        match *self {
+            RiskMode::Threshold => if s.is_completed() && s.quality >= mod_param.bonus_threshold { 1.0 } else { 0.0 },
+            _ => get_reward(s, mod_param),
+        }
+    }
+
+    // 探索で使う行動価値です。Wは評価値の総和、Nは訪問回数です
+    pub fn action_value(&self, w:f32, n:f32, histogram:Option<&ValueHistogram>) -> f32 {
+        if n == 0.0 {
+            return 0.0
+        }
+
+        match (*self, histogram) {
+            (RiskMode::Quantile(alpha), Some(h)) => quantile(h, alpha),
+            (RiskMode::CVaR(alpha), Some(h)) => cvar(h, alpha),
+            _ => w / n,
+        }
+    }
+}
+
+impl argh::FromArgValue for RiskMode {
+    fn from_arg_value(value: &str) -> Result<Self, String> {
+        RiskMode::from_name(value)
+    }
+}
+
+fn bin_center(i:usize) -> f32 {
+    (i as f32 + 0.5) / RISK_BINS as f32
+}
+
+pub fn add_to_histogram(h:&mut ValueHistogram, v:f32) {
+    add_weighted_to_histogram(h, v, 1.0);
+}
+
+// 分布を足す時は確率を重みにします
+pub fn add_weighted_to_histogram(h:&mut ValueHistogram, v:f32, weight:f32) {
+    let i = ((v.max(0.0).min(1.0) * RISK_BINS as f32) as usize).min(RISK_BINS-1);
+    h[i] += weight;
+}
+
+// 確率の合計が1の分布を、評価1回分として足します
+pub fn add_histogram(h:&mut ValueHistogram, distribution:&ValueHistogram) {
+    for (x,p) in h.iter_mut().zip(distribution.iter()) {
+        *x += p;
+    }
+}
+
+// 下側alpha分位点です。ビンの中央値で近似します
+fn quantile(h:&ValueHistogram, alpha:f32) -> f32 {
+    let total : f32 = h.iter().sum();
+    let target = alpha * total;
+    let mut sum = 0.0;
+    for i in 0..RISK_BINS {
+        sum += h[i];
+        if sum >= target && sum > 0.0 {
+            return bin_center(i)
+        }
+    }
+    bin_center(RISK_BINS-1)
+}
+
+// 下側alphaの割合の平均です
+fn cvar(h:&ValueHistogram, alpha:f32) -> f32 {
+    let total : f32 = h.iter().sum();
+    let target = alpha * total;
+    if target <= 0.0 {
+        return 0.0
+    }
+
+    let mut rest = target;
+    let mut sum = 0.0;
+    for i in 0..RISK_BINS {
+        let x = h[i].min(rest);
+        sum += x * bin_center(i);
+        rest -= x;
+        if rest <= 0.0 {
+            break
+        }
+    }
+    sum / target
+}
+
+#[test]
+fn test_risk_mode() {
+    assert_eq!(RiskMode::from_name("cvar-0.25"), Ok(RiskMode::CVaR(0.25)));
+    assert!(RiskMode::from_name("quantile-2").is_err());
+
+    // 0.0付近が1回、1.0付近が3回
+    let mut h = [0.0;RISK_BINS];
+    add_to_histogram(&mut h, 0.0);
+    for _ in 0..3 {
+        add_to_histogram(&mut h, 1.0);
+    }
+
+    assert_eq!(RiskMode::Mean.action_value(3.0, 4.0, Some(&h)), 0.75);
+    assert_eq!(RiskMode::Quantile(0.25).action_value(3.0, 4.0, Some(&h)), bin_center(0));
+    assert_eq!(RiskMode::Quantile(0.5).action_value(3.0, 4.0, Some(&h)), bin_center(RISK_BINS-1));
+    assert!((RiskMode::CVaR(0.5).action_value(3.0, 4.0, Some(&h)) - (bin_center(0) + bin_center(RISK_BINS-1)) / 2.0).abs() < 1e-6);
+}
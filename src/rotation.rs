@@ -0,0 +1,285 @@
+use num::FromPrimitive;
+
+use super::logic::{Action,State,CONDITIONS,ACTION_NUM};
+use super::setting::ModifierParameter;
+use super::simulation::{Strategy,Decision,simulate,print_summary};
+
+// 条件付きの手順(ローテーション)です。
+// 1行に1つのルールを書き、上から順に条件を満たして実行可能な最初のアクションを選びます。
+//
+//   # コメント
+//   PreciseTouch if condition == HighQuality and iq >= 8
+//   "Careful Synthesis" if progress_remaining <= 400
+//   PrudentTouch if cp >= 25 or condition == Stable
+//   BasicSynthesis
+//
+// アクション名は列挙子名・英語名・日本語名が使えます。
+// andはorより優先されます。
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+enum Field {
+    Condition,
+    InnerQuiet,
+    Cp,
+    Durability,
+    ProgressRemaining,
+    QualityRemaining,
+    Turn,
+    CarefulObservation,
+    WasteNot,
+    Veneration,
+    GreatStrides,
+    Innovation,
+    FinalAppraisal,
+    MuscleMemory,
+    Manipulation,
+    HeartAndSoul,
+    TrainedPerfection,
+    Expedience,
+    ComboBasicTouch,
+    ComboStandardTouch,
+    ComboObserve,
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+enum Op { Eq, Ne, Ge, Le, Gt, Lt }
+
+#[derive(Debug,Clone,PartialEq)]
+struct Comparison
+{
+    field : Field,
+    op : Op,
+    value : u32,                          // 状態の場合はCONDITIONSのインデックス
+}
+
+#[derive(Debug,Clone,PartialEq)]
+struct Rule
+{
+    action : Action,
+    condition : Vec<Vec<Comparison>>,     // andで結んだ比較をorで結んだもの。空なら常に真
+}
+
+pub struct Rotation
+{
+    rules : Vec<Rule>,
+}
+
+fn parse_field(name:&str) -> Result<Field, String> {
+    match name {
+        "condition" => Ok(Field::Condition),
+        "iq" | "inner_quiet" => Ok(Field::InnerQuiet),
+        "cp" => Ok(Field::Cp),
+        "durability" => Ok(Field::Durability),
+        "progress_remaining" => Ok(Field::ProgressRemaining),
+        "quality_remaining" => Ok(Field::QualityRemaining),
+        "turn" => Ok(Field::Turn),
+        "careful_observation" => Ok(Field::CarefulObservation),
+        "waste_not" => Ok(Field::WasteNot),
+        "veneration" => Ok(Field::Veneration),
+        "great_strides" => Ok(Field::GreatStrides),
+        "innovation" => Ok(Field::Innovation),
+        "final_appraisal" => Ok(Field::FinalAppraisal),
+        "muscle_memory" => Ok(Field::MuscleMemory),
+        "manipulation" => Ok(Field::Manipulation),
+        "heart_and_soul" => Ok(Field::HeartAndSoul),
+        "trained_perfection" => Ok(Field::TrainedPerfection),
+        "expedience" => Ok(Field::Expedience),
+        "combo_basic_touch" => Ok(Field::ComboBasicTouch),
+        "combo_standard_touch" => Ok(Field::ComboStandardTouch),
+        "combo_observe" => Ok(Field::ComboObserve),
+        _ => Err(format!("unknown field {}", name)),
+    }
+}
+
+fn parse_op(op:&str) -> Result<Op, String> {
+    match op {
+        "==" => Ok(Op::Eq),
+        "!=" => Ok(Op::Ne),
+        ">=" => Ok(Op::Ge),
+        "<=" => Ok(Op::Le),
+        ">" => Ok(Op::Gt),
+        "<" => Ok(Op::Lt),
+        _ => Err(format!("unknown operator {}", op)),
+    }
+}
+
+fn parse_action(name:&str) -> Result<Action, String> {
+    let name = name.trim().trim_matches('"');
+    (0..ACTION_NUM)
+        .map(|i| Action::from_usize(i).unwrap())
+        .find(|a| format!("{:?}", a) == name)
+        .or_else(|| Action::from_macro_name(name))
+        .ok_or_else(|| format!("unknown action {}", name))
+}
+
+// "field op value" を読みます。フラグ系は値を省略すると "!= 0" とみなします
+fn parse_comparison(text:&str) -> Result<Comparison, String> {
+    let xs : Vec<&str> = text.split_whitespace().collect();
+    let (field, op, value) = match xs.len() {
+        1 => (parse_field(xs[0])?, Op::Ne, "0"),
+        3 => (parse_field(xs[0])?, parse_op(xs[1])?, xs[2]),
+        _ => return Err(format!("can't parse \"{}\"", text)),
+    };
+
+    let value = if field == Field::Condition {
+        if op != Op::Eq && op != Op::Ne {
+            return Err("condition can only be compared by == or !=".to_string())
+        }
+        CONDITIONS.iter().position(|c| format!("{:?}", c) == value).ok_or_else(|| format!("unknown condition {}", value))? as u32
+    }
+    else {
+        value.parse::<u32>().map_err(|_| format!("can't parse value {}", value))?
+    };
+
+    Ok(Comparison { field, op, value })
+}
+
+fn parse_rule(line:&str) -> Result<Rule, String> {
+    let (action, condition) = match line.find(" if ") {
+        Some(i) => (&line[..i], Some(&line[i+4..])),
+        None => (line, None),
+    };
+
+    let condition = match condition {
+        Some(x) => x.split(" or ").map(|clause| clause.split(" and ").map(parse_comparison).collect::<Result<Vec<_>,_>>()).collect::<Result<Vec<_>,_>>()?,
+        None => vec![],
+    };
+
+    Ok(Rule { action:parse_action(action)?, condition })
+}
+
+fn flag(x:bool) -> u32 {
+    if x { 1 } else { 0 }
+}
+
+impl Comparison {
+    fn eval(&self, s:&State, mod_param:&ModifierParameter) -> bool {
+        let x = match self.field {
+            Field::Condition => CONDITIONS.iter().position(|c| *c == s.condition).unwrap() as u32,
+            Field::InnerQuiet => s.inner_quiet,
+            Field::Cp => s.cp,
+            Field::Durability => s.durability,
+            Field::ProgressRemaining => mod_param.max_working - s.working,
+            Field::QualityRemaining => mod_param.max_quality - s.quality,
+            Field::Turn => s.turn,
+            Field::CarefulObservation => s.careful_observation,
+            Field::WasteNot => s.waste_not,
+            Field::Veneration => s.veneration,
+            Field::GreatStrides => s.great_strides,
+            Field::Innovation => s.innovation,
+            Field::FinalAppraisal => s.final_appraisal,
+            Field::MuscleMemory => s.muscle_memory,
+            Field::Manipulation => s.manipulation,
+            Field::HeartAndSoul => flag(s.heart_and_soul),
+            Field::TrainedPerfection => flag(s.trained_perfection),
+            Field::Expedience => flag(s.expedience),
+            Field::ComboBasicTouch => flag(s.combo_basic_touch),
+            Field::ComboStandardTouch => flag(s.combo_standard_touch),
+            Field::ComboObserve => flag(s.combo_observe),
+        };
+
+        match self.op {
+            Op::Eq => x == self.value,
+            Op::Ne => x != self.value,
+            Op::Ge => x >= self.value,
+            Op::Le => x <= self.value,
+            Op::Gt => x > self.value,
+            Op::Lt => x < self.value,
+        }
+    }
+}
+
+impl Rule {
+    fn matches(&self, s:&State, mod_param:&ModifierParameter) -> bool {
+        self.condition.is_empty() || self.condition.iter().any(|clause| clause.iter().all(|x| x.eval(s, mod_param)))
+    }
+}
+
+impl Rotation {
+
+    pub fn parse(text:&str) -> Result<Rotation, String> {
+        let mut rules = vec![];
+        for (i,line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            rules.push(parse_rule(line).map_err(|e| format!("line {}: {}", i+1, e))?);
+        }
+        Ok(Rotation { rules })
+    }
+
+    // 条件を満たして実行可能な最初のアクションを返します
+    pub fn select(&self, s:&State, mod_param:&ModifierParameter) -> Option<Action> {
+        self.rules.iter()
+            .find(|r| r.matches(s, mod_param) && s.check_action(mod_param, &r.action))
+            .map(|r| r.action)
+    }
+}
+
+impl Strategy for Rotation {
+    fn reset(&mut self) {}
+
+    fn decide(&mut self, s:&State, mod_param:&ModifierParameter) -> Decision {
+        match self.select(s, mod_param) {
+            Some(a) => Decision::Run(a),
+            None => Decision::Stop,
+        }
+    }
+}
+
+pub struct SimulateRotationParameter {
+    pub mod_param : ModifierParameter,
+    pub initial_state : State,
+    pub rotation_text : String,
+    pub plays : usize,
+    pub seed : u64,
+}
+
+pub fn run_simulate_rotation( param:SimulateRotationParameter ) {
+    let mut rotation = match Rotation::parse(&param.rotation_text) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Parse error: {}", e);
+            return
+        },
+    };
+
+    let results = simulate(&mut rotation, &param.mod_param, &param.initial_state, param.plays, param.seed);
+    print_summary(&results, &param.mod_param);
+}
+
+#[test]
+fn test_rotation()
+{
+    use super::logic::Condition;
+
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let rotation = Rotation::parse("# test\nPreciseTouch if condition == HighQuality and iq >= 8\n\"Prudent Touch\" if waste_not == 0 or cp >= 1000\nBasicTouch\n").unwrap();
+    let s = State { turn:5, .. State::new(&mod_param) };
+
+    assert_eq!( Some(Action::PreciseTouch), rotation.select(&State { condition:Condition::HighQuality, inner_quiet:8, .. s.clone() }, &mod_param) );
+    assert_eq!( Some(Action::PrudentTouch), rotation.select(&State { condition:Condition::HighQuality, inner_quiet:7, .. s.clone() }, &mod_param) );
+    assert_eq!( Some(Action::BasicTouch), rotation.select(&State { waste_not:2, .. s.clone() }, &mod_param) );
+    assert!( Rotation::parse("PreciseTouch if condition >= HighQuality").is_err() );
+}
+
+#[test]
+fn test_rotation_stop()
+{
+    use super::simulation::play_once;
+    use super::seed::new_rng;
+    use super::logic::Modifier;
+
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let s = State { turn:2, .. State::new(&mod_param) };
+    let mut modifier = Modifier { mod_param:mod_param.clone(), rng:new_rng(1), forced_success:None };
+
+    // 条件を満たす手が無ければ、何もせずに未完成のまま終わります
+    let mut rotation = Rotation::parse("PreciseTouch if condition == HighQuality").unwrap();
+    assert!( matches!(rotation.decide(&s, &mod_param), Decision::Stop) );
+
+    let result = play_once(&mut rotation, &s, &mut modifier);
+    assert!( !result.aborted );
+    assert_eq!( s, result.state );
+}
@@ -0,0 +1,372 @@
+use std::sync::Arc;
+
+use super::logic::{State,Action};
+
+// パッチごとに異なる製作ルールです。
+// 必要CP・効率・消費耐久・バフの効果ターン数などはここから取得します。
+// 状態(高能率・頑丈・高持続・安定)による補正はパッチ共通なのでState側で行います。
+pub trait Ruleset
+{
+    // このパッチで利用できるアクションかどうか
+    fn is_available(&self, a:&Action) -> bool;
+
+    // 以下は既定で共通の表を使います。パッチごとに違う行だけ、各ルールで上書きします
+
+    // 必要CP(高能率による補正前)
+    fn base_cp(&self, s:&State, a:&Action) -> u32 {
+        common_cp(s, a)
+    }
+
+    // 作業効率(作業しないアクションは0)
+    fn progress_efficiency(&self, _s:&State, a:&Action) -> u32 {
+        common_progress_efficiency(a)
+    }
+
+    // 加工効率(加工しないアクションは0)
+    fn quality_efficiency(&self, s:&State, a:&Action) -> u32 {
+        common_quality_efficiency(s, a)
+    }
+
+    // 加工時に増えるインナークワイエットのスタック数
+    fn inner_quiet_stack(&self, _s:&State, a:&Action) -> u32 {
+        common_inner_quiet_stack(a)
+    }
+
+    // 消費耐久(倹約・頑丈による補正前)
+    fn durability_cost(&self, a:&Action) -> u32 {
+        common_durability_cost(a)
+    }
+
+    // バフの効果ターン数(高持続による補正前)
+    fn buff_duration(&self, a:&Action) -> u32 {
+        common_buff_duration(a)
+    }
+
+    // 成功率(安定による補正前)
+    fn success_rate(&self, a:&Action) -> f32 {
+        common_success_rate(a)
+    }
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum RulesetType {
+    Shadowbringers,
+    Endwalker,
+    Dawntrail,
+}
+
+impl RulesetType {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "shadowbringers" => Ok(RulesetType::Shadowbringers),
+            "endwalker" => Ok(RulesetType::Endwalker),
+            "dawntrail" => Ok(RulesetType::Dawntrail),
+            _ => Err("unknown ruleset type".to_string()),
+        }
+    }
+}
+
+impl argh::FromArgValue for RulesetType {
+    fn from_arg_value(value: &str) -> Result<Self, String> {
+        RulesetType::from_name(value)
+    }
+}
+
+pub fn create_ruleset(ruleset_type: RulesetType) -> Arc<dyn Ruleset + Sync + Send> {
+    match ruleset_type {
+        RulesetType::Shadowbringers => Arc::new(Shadowbringers {}),
+        RulesetType::Endwalker => Arc::new(Endwalker {}),
+        RulesetType::Dawntrail => Arc::new(Dawntrail {}),
+    }
+}
+
+// 漆黒(5.x)のルールです。イシュガルド第四次復興時に利用していました。
+// 当時のインナークワイエットはアクションでしたが、アクション一覧に存在しないため
+// 真価で付与されたものとして、加工の度に自動でスタックする近似をしています。
+#[derive(Debug,Clone)]
+pub struct Shadowbringers {}
+
+// 暁月(6.x)のルールです。
+#[derive(Debug,Clone)]
+pub struct Endwalker {}
+
+// 黄金(7.x)のルールです。
+// 注視作業・注視加工が削除され、経過観察は上級加工へのコンボになりました。
+#[derive(Debug,Clone)]
+pub struct Dawntrail {}
+
+// 全パッチ共通の表です。暁月の値を基本にして、そのパッチに存在しないアクションの行は無視されます
+
+fn common_cp(s:&State, a:&Action) -> u32 {
+    match a {
+        Action::BasicSynthesis => 0,
+        Action::BasicTouch => 18,
+        Action::MastersMend => 88,
+        Action::HastyTouch => 0,
+        Action::RapidSynthesis => 0,
+        Action::Observe => 7,
+        Action::TricksOfTheTrade => 0,
+        Action::WasteNot => 56,
+        Action::Veneration => 18,
+        Action::StandardTouch => if s.combo_basic_touch { 18 } else { 32 },
+        Action::GreatStrides => 32,
+        Action::Innovation => 18,
+        Action::FinalAppraisal => 1,
+        Action::WasteNot2 => 98,
+        Action::ByregotsBlessing => 24,
+        Action::PreciseTouch => 18,
+        Action::MuscleMemory => 6,
+        Action::CarefulObservation => 0,
+        Action::CarefulSynthesis => 7,
+        Action::Manipulation => 96,
+        Action::PrudentTouch => 25,
+        Action::FocusedSynthesis => 5,
+        Action::FocusedTouch => 18,
+        Action::Reflect => 6,
+        Action::PreparatoryTouch => 40,
+        Action::Groundwork => 18,
+        Action::DelicateSynthesis => 32,
+        Action::IntensiveSynthesis => 6,
+        Action::AdvancedTouch => if s.combo_standard_touch { 18 } else { 46 },
+        Action::HeartAndSoul => 0,
+        Action::PrudentSynthesis => 18,
+        Action::TrainedFinesse => 32,
+        Action::ImmaculateMend => 112,
+        Action::TrainedPerfection => 0,
+        Action::DaringTouch => 0,
+        Action::RefinedTouch => 24,
+        Action::QuickInnovation => 0,
+    }
+}
+
+fn common_progress_efficiency(a:&Action) -> u32 {
+    match a {
+        Action::BasicSynthesis => 120,
+        Action::RapidSynthesis => 500,
+        Action::MuscleMemory => 300,
+        Action::CarefulSynthesis => 180,
+        Action::FocusedSynthesis => 150,
+        Action::Groundwork => 360,
+        Action::DelicateSynthesis => 100,
+        Action::IntensiveSynthesis => 400,
+        Action::PrudentSynthesis => 180,
+        _ => 0,
+    }
+}
+
+fn common_quality_efficiency(s:&State, a:&Action) -> u32 {
+    match a {
+        Action::BasicTouch => 100,
+        Action::HastyTouch => 100,
+        Action::StandardTouch => 125,
+        Action::ByregotsBlessing => 100 + s.inner_quiet * 20,
+        Action::PreciseTouch => 150,
+        Action::PrudentTouch => 100,
+        Action::FocusedTouch => 150,
+        Action::Reflect => 100,
+        Action::PreparatoryTouch => 200,
+        Action::DelicateSynthesis => 100,
+        Action::AdvancedTouch => 150,
+        Action::TrainedFinesse => 100,
+        Action::DaringTouch => 150,
+        Action::RefinedTouch => 100,
+        _ => 0,
+    }
+}
+
+fn common_inner_quiet_stack(a:&Action) -> u32 {
+    match a {
+        Action::PreciseTouch => 2,
+        Action::Reflect => 2,
+        Action::PreparatoryTouch => 2,
+        _ => 1,
+    }
+}
+
+fn common_durability_cost(a:&Action) -> u32 {
+    match a {
+        Action::BasicSynthesis => 10,
+        Action::BasicTouch => 10,
+        Action::HastyTouch => 10,
+        Action::RapidSynthesis => 10,
+        Action::StandardTouch => 10,
+        Action::ByregotsBlessing => 10,
+        Action::PreciseTouch => 10,
+        Action::MuscleMemory => 10,
+        Action::CarefulSynthesis => 10,
+        Action::PrudentTouch => 5,
+        Action::FocusedSynthesis => 10,
+        Action::FocusedTouch => 10,
+        Action::Reflect => 10,
+        Action::PreparatoryTouch => 20,
+        Action::Groundwork => 20,
+        Action::DelicateSynthesis => 10,
+        Action::IntensiveSynthesis => 10,
+        Action::AdvancedTouch => 10,
+        Action::PrudentSynthesis => 5,
+        Action::DaringTouch => 10,
+        Action::RefinedTouch => 10,
+        _ => 0,
+    }
+}
+
+fn common_buff_duration(a:&Action) -> u32 {
+    match a {
+        Action::WasteNot => 4,
+        Action::Veneration => 4,
+        Action::GreatStrides => 3,
+        Action::Innovation => 4,
+        Action::FinalAppraisal => 5,
+        Action::WasteNot2 => 8,
+        Action::MuscleMemory => 5,
+        Action::Manipulation => 8,
+        Action::QuickInnovation => 1,
+        _ => 0,
+    }
+}
+
+fn common_success_rate(a:&Action) -> f32 {
+    match a {
+        Action::HastyTouch => 0.6,
+        Action::RapidSynthesis => 0.5,
+        Action::FocusedSynthesis => 0.5,
+        Action::FocusedTouch => 0.5,
+        Action::DaringTouch => 0.6,
+        _ => 1.0,
+    }
+}
+
+impl Ruleset for Shadowbringers {
+    fn is_available(&self, a:&Action) -> bool {
+        match a {
+            Action::AdvancedTouch => false,
+            Action::HeartAndSoul => false,
+            Action::PrudentSynthesis => false,
+            Action::TrainedFinesse => false,
+            Action::ImmaculateMend => false,
+            Action::TrainedPerfection => false,
+            Action::DaringTouch => false,
+            Action::RefinedTouch => false,
+            Action::QuickInnovation => false,
+            _ => true,
+        }
+    }
+
+    fn base_cp(&self, s:&State, a:&Action) -> u32 {
+        match a {
+            Action::Reflect => 24,
+            _ => common_cp(s, a),
+        }
+    }
+
+    fn progress_efficiency(&self, _s:&State, a:&Action) -> u32 {
+        match a {
+            Action::CarefulSynthesis => 150,
+            Action::FocusedSynthesis => 200,
+            Action::Groundwork => 300,
+            Action::IntensiveSynthesis => 300,
+            _ => common_progress_efficiency(a),
+        }
+    }
+
+    fn inner_quiet_stack(&self, _s:&State, a:&Action) -> u32 {
+        match a {
+            Action::Reflect => 3,
+            _ => common_inner_quiet_stack(a),
+        }
+    }
+}
+
+impl Ruleset for Endwalker {
+    fn is_available(&self, a:&Action) -> bool {
+        match a {
+            Action::ImmaculateMend => false,
+            Action::TrainedPerfection => false,
+            Action::DaringTouch => false,
+            Action::RefinedTouch => false,
+            Action::QuickInnovation => false,
+            _ => true,
+        }
+    }
+
+    fn success_rate(&self, a:&Action) -> f32 {
+        match a {
+            Action::HastyTouch => 0.5,
+            _ => common_success_rate(a),
+        }
+    }
+}
+
+impl Ruleset for Dawntrail {
+    fn is_available(&self, a:&Action) -> bool {
+        match a {
+            Action::FocusedSynthesis => false,
+            Action::FocusedTouch => false,
+            _ => true,
+        }
+    }
+
+    fn base_cp(&self, s:&State, a:&Action) -> u32 {
+        match a {
+            Action::AdvancedTouch => if s.combo_standard_touch || s.combo_observe { 18 } else { 46 },
+            _ => common_cp(s, a),
+        }
+    }
+
+    fn progress_efficiency(&self, _s:&State, a:&Action) -> u32 {
+        match a {
+            Action::DelicateSynthesis => 150,
+            _ => common_progress_efficiency(a),
+        }
+    }
+
+    fn quality_efficiency(&self, s:&State, a:&Action) -> u32 {
+        match a {
+            Action::Reflect => 300,
+            _ => common_quality_efficiency(s, a),
+        }
+    }
+
+    fn inner_quiet_stack(&self, s:&State, a:&Action) -> u32 {
+        match a {
+            Action::RefinedTouch => if s.combo_basic_touch { 2 } else { 1 }, // 加工からのコンボでスタックが増えます
+            _ => common_inner_quiet_stack(a),
+        }
+    }
+}
+
+#[test]
+fn test_shadowbringers_availability()
+{
+    let ruleset = create_ruleset(RulesetType::Shadowbringers);
+    for a in &[Action::AdvancedTouch, Action::HeartAndSoul, Action::PrudentSynthesis, Action::TrainedFinesse,
+               Action::ImmaculateMend, Action::TrainedPerfection, Action::DaringTouch, Action::RefinedTouch, Action::QuickInnovation] {
+        assert!(!ruleset.is_available(a), "{:?}", a);
+    }
+    assert!(ruleset.is_available(&Action::FocusedTouch));
+
+    // パッチ7.0の追加アクションはルールセット越しにも使えません
+    let mut mod_param = super::setting::ModifierParameter::new_ishgard_reconstruction_4th();
+    mod_param.ruleset = ruleset;
+    let s = State::new(&mod_param);
+    assert!(!s.check_action(&mod_param, &Action::ImmaculateMend));
+}
+
+#[test]
+fn test_ruleset_overrides()
+{
+    let mod_param = super::setting::ModifierParameter::new_fountain_of_usouso();
+    let s = State::new(&mod_param);
+    let sb = create_ruleset(RulesetType::Shadowbringers);
+    let ew = create_ruleset(RulesetType::Endwalker);
+    let dt = create_ruleset(RulesetType::Dawntrail);
+
+    // 上書きした行だけがパッチごとに違い、それ以外は共通の表の値です
+    assert_eq!( (24, 6, 6), (sb.base_cp(&s, &Action::Reflect), ew.base_cp(&s, &Action::Reflect), dt.base_cp(&s, &Action::Reflect)) );
+    assert_eq!( (0.6, 0.5, 0.6), (sb.success_rate(&Action::HastyTouch), ew.success_rate(&Action::HastyTouch), dt.success_rate(&Action::HastyTouch)) );
+    assert_eq!( (100, 100, 300), (sb.quality_efficiency(&s, &Action::Reflect), ew.quality_efficiency(&s, &Action::Reflect), dt.quality_efficiency(&s, &Action::Reflect)) );
+
+    // 経過観察から上級加工へのコンボは黄金だけです
+    let observed = State { combo_observe:true, .. s.clone() };
+    assert_eq!( (46, 18), (ew.base_cp(&observed, &Action::AdvancedTouch), dt.base_cp(&observed, &Action::AdvancedTouch)) );
+}
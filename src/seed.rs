@@ -0,0 +1,45 @@
+use std::time::SystemTime;
+
+use xorshift::{SeedableRng,Xorshift128};
+
+// 乱数のシードです。
+// --seedが指定されなければ時刻から作り、スレッドやエピソードごとのシードはここから導出します
+
+// 時刻から作るシードです
+pub fn new_seed() -> u64 {
+    From::from( SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).expect("Failed to get UNIXTIME").subsec_nanos() )
+}
+
+// splitmix64で値を攪拌します
+fn mix(x:u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+// 親シードと番号から子のシードを導出します
+pub fn derive_seed(seed:u64, index:u64) -> u64 {
+    mix(mix(seed) ^ index)
+}
+
+// Xorshift128は状態が全て0だと同じ値しか出さないので、攪拌した値で初期化します
+pub fn new_rng(seed:u64) -> Xorshift128 {
+    let seeds = [mix(seed), mix(seed ^ 0xFFFFFFFFFFFFFFFF)];
+    SeedableRng::from_seed(&seeds[..])
+}
+
+#[test]
+fn test_seed() {
+    use xorshift::Rng;
+
+    let mut a = new_rng(1);
+    let mut b = new_rng(1);
+    let xs : Vec<u64> = (0..10).map(|_| a.next_u64()).collect();
+    let ys : Vec<u64> = (0..10).map(|_| b.next_u64()).collect();
+    assert_eq!(xs, ys);
+
+    assert_eq!(derive_seed(1,0), derive_seed(1,0));
+    assert_ne!(derive_seed(1,0), derive_seed(1,1));
+    assert_ne!(derive_seed(1,0), derive_seed(2,0));
+}
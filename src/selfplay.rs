@@ -2,22 +2,26 @@
 use std::sync::{Arc,Mutex};
 use std::sync::mpsc::{channel,Sender,Receiver,TryRecvError};
 use std::thread::JoinHandle;
-use std::time::{Instant,SystemTime,Duration};
-use std::cell::RefCell;
+use std::time::{Instant,Duration};
+use std::cell::{Cell,RefCell};
 use std::rc::Rc;
+use std::future::Future;
+use std::task::{Context,Poll};
 
 use mysql::*;
+use noop_waker::noop_waker;
 use serde::{Serialize,Deserialize};
-use xorshift::{SeedableRng};
 
 use super::selector::{Selector,UCB1Context};
 use super::logic::{State,Action,Modifier};
 use super::setting::ModifierParameter;
-use super::mcts::{MCTSContext,ActionVector,select_action_weighted,select_action_greedy,get_reward};
+use super::mcts::{MCTSContext,select_action_weighted,select_action_greedy};
+use super::risk::RiskMode;
 use super::writer::*;
 use super::cache::*;
 use super::executor::*;
 use super::predictor::*;
+use super::seed::{derive_seed,new_rng};
 use super::network::*;
 
 #[derive(Debug,Clone)]
@@ -29,10 +33,13 @@ pub enum WriterParameter {
 #[derive(Clone)]
 pub struct EpisodeParameter {
     pub mod_param : ModifierParameter,
+    pub initial_state : State,            // MCTSの探索もこの状態から始めます
     pub mcts_simulation_num : u32,
     pub alpha : f32,
     pub eps : f32,
     pub start_greedy_turn : u32,
+    pub seed : u64,                       // スレッドごと・エピソードごとのシードはここから導出します
+    pub risk_mode : RiskMode,             // MCTSで最大化する指標
 }
 
 #[derive(Clone)]
@@ -52,15 +59,53 @@ pub struct SelfPlayParameter {
 pub struct Sample {
     pub action : Action, // 無くても問題ないけどログ見るのに便利なので出しておく
     pub state : State,
-    pub mcts_policy : ActionVector,
+    pub mcts_policy : Vec<f32>, // serdeは33要素以上の配列に対応していないのでVecにしています
 }
 
+// レコードはbincodeで保存します。bincodeは自己記述的ではないので、
+// Record・Sampleや、その中のStateの構造を変えた時はRECORD_VERSIONを上げてください
+const RECORD_MAGIC : [u8;4] = *b"CREC";
+pub const RECORD_VERSION : u32 = 1;
+
 #[derive(Serialize,Deserialize,Debug)]
 pub struct Record {
     pub samples : Vec<Sample>,
     pub name : String,
     pub last_state : State,
     pub reward : f32,
+
+    // 以下はエピソードを再現するための設定です。nameのモデルと同じ設定で、このシードから同じ結果になります
+    pub seed : u64,                       // このエピソードの乱数シード
+    pub network_type : NetworkType,
+    pub mcts_simulation_num : u32,
+    pub alpha : f32,
+    pub eps : f32,
+    pub start_greedy_turn : u32,
+    pub risk_mode : RiskMode,
+}
+
+// 先頭にマジックナンバーとバージョンを付けてシリアライズします
+pub fn encode_records( records:&Vec<Record> ) -> Vec<u8> {
+    let mut encoded = RECORD_MAGIC.to_vec();
+    encoded.extend_from_slice(&RECORD_VERSION.to_le_bytes());
+    encoded.extend(bincode::serialize(records).unwrap());
+    encoded
+}
+
+// バージョンが違うレコードはStateの構造が違うので読めません
+pub fn decode_records( encoded:&[u8] ) -> std::result::Result<Vec<Record>,String> {
+    if encoded.len() < 8 || encoded[0..4] != RECORD_MAGIC {
+        return Err("unversioned record format is not supported".to_string())
+    }
+
+    let mut version = [0u8;4];
+    version.copy_from_slice(&encoded[4..8]);
+    let version = u32::from_le_bytes(version);
+    if version != RECORD_VERSION {
+        return Err(format!("record version {} is not supported(expected {})", version, RECORD_VERSION))
+    }
+
+    bincode::deserialize(&encoded[8..]).map_err(|e| e.to_string())
 }
 
 struct ThreadContext {
@@ -74,21 +119,20 @@ struct CoroutineContext {
     episode_param : EpisodeParameter,
     writer_sender : Sender<Record>,
     predict_queue : PredictQueue,
+    episode_count : Cell<u64>,
     graph_info : RefCell<(String,Arc<(NetworkType,tch::nn::VarStore)>)>, // CellはCopy traitを要求します。StringもArcもCloneが無いのでRefCellが必要であるようです
 }
 
-async fn selfplay_craftone( param:&EpisodeParameter, graph_filename:&String, predict_queue:&PredictQueue ) -> Record {
+async fn selfplay_craftone( param:&EpisodeParameter, seed:u64, graph_filename:&String, network_type:NetworkType, predict_queue:&PredictQueue ) -> Record {
 
-    let seed : u64 = From::from( SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).expect("Failed to get UNIXTIME").subsec_nanos() );
-    let seeds = [seed, seed];
-    let mut modifier = Modifier { mod_param:param.mod_param.clone(), rng:SeedableRng::from_seed(&seeds[..]) };
+    let mut modifier = Modifier { mod_param:param.mod_param.clone(), rng:new_rng(seed), forced_success:None };
 
     let mut samples = vec![];
-    let mut state = State::new(&param.mod_param);
+    let mut state = param.initial_state.clone();
 
     // コンテキストを１手ごとに初期化するかゲーム中で完全記憶するのが良いかが分かりませんが、一旦ここにしておきます。
     // 多分こっちのほうが良いんだけどメモリは使います
-    let mut mcts_context = MCTSContext::new(1.0, param.alpha, param.eps, predict_queue.clone(), graph_filename.clone());
+    let mut mcts_context = MCTSContext::new(1.0, param.alpha, param.eps, param.risk_mode, predict_queue.clone(), graph_filename.clone());
 
     while !state.is_terminated() {
         let mcts_policy = mcts_context.search(&state, &mut modifier, param.mcts_simulation_num).await;
@@ -100,22 +144,60 @@ async fn selfplay_craftone( param:&EpisodeParameter, graph_filename:&String, pre
             select_action_greedy(&mcts_policy, &mut modifier.rng)
         };
 
-        samples.push( Sample { action:action.clone(), state:state.clone(), mcts_policy:mcts_policy } );
+        samples.push( Sample { action:action.clone(), state:state.clone(), mcts_policy:mcts_policy.to_vec() } );
 
         state = state.run_action(&mut modifier,&action);
     }
 
     // 最終的な報酬を計算します。
-    let reward = get_reward(&state,&modifier.mod_param);
+    let reward = param.risk_mode.terminal_value(&state,&modifier.mod_param);
 
     // 結果を返す
-    Record { samples:samples, name:graph_filename.clone(), last_state:state, reward:reward }
+    Record {
+        samples:samples,
+        name:graph_filename.clone(),
+        last_state:state,
+        reward:reward,
+        seed:seed,
+        network_type:network_type,
+        mcts_simulation_num:param.mcts_simulation_num,
+        alpha:param.alpha,
+        eps:param.eps,
+        start_greedy_turn:param.start_greedy_turn,
+        risk_mode:param.risk_mode,
+    }
+}
+
+// 1エピソードを最後まで実行します。predictは溜まった予測を処理します
+fn run_episode_with<P:FnMut(&mut Predictor)>( param:&EpisodeParameter, seed:u64, graph_filename:&String, network_type:NetworkType, predictor:&mut Predictor, mut predict:P ) -> Record {
+    let waker = noop_waker();
+    let mut ctx = Context::from_waker(&waker);
+    let predict_queue = predictor.get_queue();
+    let mut future = Box::pin(selfplay_craftone(param, seed, graph_filename, network_type, &predict_queue));
+
+    loop {
+        if let Poll::Ready(x) = future.as_mut().poll(&mut ctx) {
+            return x
+        }
+        predict(predictor);
+    }
+}
+
+// 保存されたエピソードのシードから、そのエピソードだけを再実行します
+// エピソードのシードはスレッド番号とエピソード番号から導出しているので、--seedだけでは個々のエピソードを指定できません
+pub fn run_episode( param:&EpisodeParameter, seed:u64, graph_filename:&String, graph:&(NetworkType,tch::nn::VarStore) ) -> Record {
+    let mut predictor = Predictor::new();
+    predictor.load_network(graph_filename.clone(), graph);
+    run_episode_with(param, seed, graph_filename, graph.0, &mut predictor, |p| p.predict_batch(&param.mod_param))
 }
 
 async fn selfplay_coroutine( co_ctx:Rc<CoroutineContext> ) {
     loop {
-        let (graph_filename,_) = co_ctx.graph_info.borrow().clone();
-        let record = selfplay_craftone(&co_ctx.episode_param, &graph_filename, &co_ctx.predict_queue);
+        let (graph_filename,graph) = co_ctx.graph_info.borrow().clone();
+        let episode = co_ctx.episode_count.get();
+        co_ctx.episode_count.set(episode + 1);
+        let seed = derive_seed(co_ctx.episode_param.seed, episode);
+        let record = selfplay_craftone(&co_ctx.episode_param, seed, &graph_filename, graph.0, &co_ctx.predict_queue);
         co_ctx.writer_sender.send(record.await).unwrap();
     }
 }
@@ -136,6 +218,7 @@ fn selfplay_thread( ctx:ThreadContext ) {
         episode_param:ctx.episode_param,
         writer_sender:ctx.writer_sender,
         predict_queue:predictor.get_queue(),
+        episode_count:Cell::new(0),
         graph_info:RefCell::new(graph_info),
     });
 
@@ -172,8 +255,10 @@ fn spawn_selfplay_threads( episode_param:&EpisodeParameter, writer_sender:&Sende
     let mut senders = vec![];
     for thread_id in 0..thread_num {
         let (sender,receiver) = channel();
+        let mut thread_episode_param = episode_param.clone();
+        thread_episode_param.seed = derive_seed(episode_param.seed, thread_id as u64);
         let ctx = ThreadContext {
-            episode_param:episode_param.clone(),
+            episode_param:thread_episode_param,
             batch_size:batch_size,
             selfplay_receiver:receiver,
             writer_sender:writer_sender.clone(),
@@ -300,3 +385,57 @@ pub fn run(param:&SelfPlayParameter) {
 
     run_simulation(param);
 }
+
+#[cfg(test)]
+fn test_episode_param( seed:u64 ) -> EpisodeParameter {
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    EpisodeParameter {
+        initial_state: State::new(&mod_param),
+        mod_param: mod_param,
+        mcts_simulation_num: 8,
+        alpha: 0.15,
+        eps: 0.25,
+        start_greedy_turn: 5,
+        seed: seed,
+        risk_mode: RiskMode::Mean,
+    }
+}
+
+#[test]
+fn test_episode_determinism()
+{
+    use super::mcts::ActionVector;
+    use super::logic::ACTION_NUM;
+
+    let param = test_episode_param(12345);
+    let name = "test".to_string();
+    let network_type = NetworkType::FullyConnected(1, 1, ValueHead::Scalar);
+    let uniform = |_:&State| -> (ActionVector,f32) { ([1.0 / ACTION_NUM as f32; ACTION_NUM], 0.5) };
+
+    let run = |seed| {
+        let mut predictor = Predictor::new();
+        run_episode_with(&param, seed, &name, network_type, &mut predictor, |p| p.predict_batch_with(uniform))
+    };
+
+    // 同じシードなら同じレコードになります
+    let x = encode_records(&vec![run(1)]);
+    let y = encode_records(&vec![run(1)]);
+    assert_eq!( x, y );
+
+    let records = decode_records(&x).unwrap();
+    assert_eq!( 1, records[0].seed );
+    assert_eq!( network_type, records[0].network_type );
+}
+
+#[test]
+fn test_decode_records_version()
+{
+    let records : Vec<Record> = vec![];
+
+    // バージョンの無い旧形式や、違うバージョンは読みません
+    assert!( decode_records(&bincode::serialize(&records).unwrap()).is_err() );
+
+    let mut encoded = encode_records(&records);
+    encoded[4] = encoded[4].wrapping_add(1);
+    assert!( decode_records(&encoded).is_err() );
+}
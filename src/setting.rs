@@ -1,10 +1,18 @@
 use std::sync::Arc;
 use std::collections::HashMap;
+use serde::{Serialize,Deserialize};
+
+use super::ruleset::{Ruleset,Shadowbringers,Endwalker};
+use super::condition::ConditionModel;
+use super::capability::Capability;
+use super::timing::TimingModel;
+use super::target::{Collectable,Scoring};
+use super::reward::{RewardModel,BlendReward};
 
 pub trait AdvanceTable
 {
     fn working_advance(&self, efficiency:u32, high_progress:bool, veneration:bool, muscle_memory:bool) -> u32;
-    fn quality_advance(&self, efficiency:u32, high_quality:bool, innovation:bool, grate_strides:bool, inner_quiet:u32) -> u32;
+    fn quality_advance(&self, efficiency:u32, condition_rate:u32, innovation:bool, grate_strides:bool, inner_quiet:u32) -> u32; // condition_rateは状態補正(%)
 }
 
 // イシュガルド第四次復興時に利用していたロジックです。
@@ -50,6 +58,68 @@ struct SimpleTable
     quality_base : HashMap<QualityKey,u32>,
 }
 
+// 製作者のステータスです。
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct CrafterStatus
+{
+    pub craftsmanship : u32,              // 作業精度
+    pub control : u32,                    // 加工精度
+    pub cp : u32,                         // CP
+    pub level : u32,                      // クラスレベル
+
+    #[serde(default)]
+    pub specialist : bool,                // スペシャリストかどうか
+}
+
+// レシピのステータスです。
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct RecipeStatus
+{
+    pub class_job_level : u32,            // レシピレベルに対応するクラスレベル
+    pub difficulty : u32,                 // 必要工数
+    pub quality : u32,                    // 品質上限
+    pub durability : u32,                 // 初期耐久
+    pub progress_divider : u32,           // 作業の除数
+    pub quality_divider : u32,            // 加工の除数
+    pub progress_modifier : u32,          // 作業の補正(%)
+    pub quality_modifier : u32,           // 加工の補正(%)
+}
+
+// ステータスから計算式で求めるテーブルです。
+// 暁月以降の計算式で、切り捨ての順序もゲームに合わせて整数演算しています。
+#[derive(Debug,Clone)]
+struct FormulaTable
+{
+    // 効率100%・補正なしの作業量
+    base_progress : u64,
+
+    // 効率100%・補正なしの品質
+    base_quality : u64,
+}
+
+// レシピファイルを使わない、実測値を元にした設定です
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum Preset {
+    Usouso,       // ウソウソの泉(暁月)
+    Ishgard4,     // イシュガルド第四次復興(漆黒)
+}
+
+impl Preset {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "usouso" => Ok(Preset::Usouso),
+            "ishgard4" => Ok(Preset::Ishgard4),
+            _ => Err("unknown preset".to_string()),
+        }
+    }
+}
+
+impl argh::FromArgValue for Preset {
+    fn from_arg_value(value: &str) -> Result<Self, String> {
+        Preset::from_name(value)
+    }
+}
+
 #[derive(Clone)]
 pub struct ModifierParameter
 {
@@ -58,17 +128,51 @@ pub struct ModifierParameter
     pub max_durability : u32,             // 初期耐久
     pub max_cp : u32,                     // 初期CP
     pub advance_table : Arc<dyn AdvanceTable + Sync + Send>, // これをArcにしないと多くの関数がGenericになってしまうのでArcにしてます
+    pub ruleset : Arc<dyn Ruleset + Sync + Send>, // パッチごとの製作ルール
+    pub condition_model : ConditionModel, // 状態の遷移モデル
+    pub capability : Capability,          // 製作者のレベル・スペシャリスト
+    pub timing : TimingModel,             // アクションの所要時間と時間報酬の曲線
     pub bonus_time_t : f32,               // 時間ボーナス割合
     pub bonus_threshold_t : f32,          // 閾値ボーナス割合
     pub bonus_threshold : u32,            // 閾値ボーナス最低値
+    pub reward_model : Arc<dyn RewardModel + Sync + Send>, // 終局状態の報酬
+    pub collectable : Option<Collectable>, // 収集品の段階(収集品のみ)
+    pub scoring : Option<Scoring>,        // 納品イベントのスコア表
 }
 
 impl ModifierParameter {
 
+    // 製作者とレシピのステータスから計算します
+    pub fn new_from_status(crafter:&CrafterStatus, recipe:&RecipeStatus) -> ModifierParameter {
+        ModifierParameter {
+            max_working : recipe.difficulty,
+            max_quality : recipe.quality,
+            max_durability : recipe.durability,
+            max_cp : crafter.cp,
+            advance_table : Arc::new( FormulaTable::new(crafter, recipe) ),
+            ruleset : Arc::new( Endwalker {} ),
+            condition_model : ConditionModel::new_normal_recipe(),
+            capability : Capability { level:crafter.level, specialist:crafter.specialist },
+            timing : TimingModel::default(),
+            bonus_time_t : 0.15,
+            bonus_threshold_t : 0.50,
+            bonus_threshold : recipe.quality, // max値の時のみ有効
+            reward_model : Arc::new( BlendReward {} ),
+            collectable : None,
+            scoring : None,
+        }
+    }
+
+    pub fn new_from_preset(preset:Preset) -> ModifierParameter {
+        match preset {
+            Preset::Usouso => ModifierParameter::new_fountain_of_usouso(),
+            Preset::Ishgard4 => ModifierParameter::new_ishgard_reconstruction_4th(),
+        }
+    }
+
     // 作業精度2769
     // 加工精度2840
     // maxcp 569
-    #[allow(dead_code)]
     pub fn new_ishgard_reconstruction_4th() -> ModifierParameter {
         ModifierParameter {
             max_working : 12046,
@@ -80,9 +184,16 @@ impl ModifierParameter {
                 process_accuracy : 2840 + 70,
                 required_process_accuracy : 2540,
             }),
+            ruleset : Arc::new( Shadowbringers {} ),
+            condition_model : ConditionModel::new_ishgard_reconstruction_4th(),
+            capability : Capability::new_max(),
+            timing : TimingModel::default(),
             bonus_time_t : 0.15,
             bonus_threshold_t : 0.50,
             bonus_threshold : 81447, // max値の時のみ有効
+            reward_model : Arc::new( BlendReward {} ),
+            collectable : None,
+            scoring : Some( Scoring::new_ishgard_restoration() ),
         }
     }
 
@@ -136,13 +247,75 @@ impl ModifierParameter {
                 work_base : 209,
                 quality_base : table,
             }),
+            ruleset : Arc::new( Endwalker {} ),
+            condition_model : ConditionModel::new_fountain_of_usouso(),
+            capability : Capability::new_max(),
+            timing : TimingModel::default(),
             bonus_time_t : 0.15,
             bonus_threshold_t : 0.50,
             bonus_threshold : 13500, // ウソウソの泉作成要件
+            reward_model : Arc::new( BlendReward {} ),
+            collectable : None,
+            scoring : None,
         }
     }
 }
 
+impl CrafterStatus {
+
+    // ウソウソの泉の計測時のステータスです(CPのみ食事・薬込み)
+    pub fn new_fountain_of_usouso() -> CrafterStatus {
+        CrafterStatus {
+            craftsmanship : 3738,
+            control : 3768,
+            cp : 588 + 78 + 21,
+            level : 90,
+            specialist : true,
+        }
+    }
+}
+
+impl FormulaTable {
+    pub fn new(crafter:&CrafterStatus, recipe:&RecipeStatus) -> FormulaTable {
+        let craftsmanship = crafter.craftsmanship as u64;
+        let control = crafter.control as u64;
+        let progress_divider = recipe.progress_divider as u64;
+        let quality_divider = recipe.quality_divider as u64;
+
+        // レシピのクラスレベル以下の場合だけ補正が掛かります
+        let (progress_modifier, quality_modifier) = if crafter.level <= recipe.class_job_level {
+            (recipe.progress_modifier as u64, recipe.quality_modifier as u64)
+        }
+        else {
+            (100, 100)
+        };
+
+        // floor((作業精度*10/除数 + 2) * 補正/100) を整数で計算します
+        let base_progress = (craftsmanship * 10 + 2 * progress_divider) * progress_modifier / (progress_divider * 100);
+        let base_quality = (control * 10 + 35 * quality_divider) * quality_modifier / (quality_divider * 100);
+
+        FormulaTable { base_progress, base_quality }
+    }
+}
+
+impl AdvanceTable for FormulaTable {
+    fn working_advance(&self, efficiency:u32, high_progress:bool, veneration:bool, muscle_memory:bool) -> u32 {
+        let cond_rate = if high_progress { 150 } else { 100 };
+        let buff_rate = 100 + if veneration { 50 } else { 0 } + if muscle_memory { 100 } else { 0 };
+
+        let base = self.base_progress * cond_rate / 100;
+        ( base * efficiency as u64 * buff_rate / 10000 ) as u32
+    }
+
+    fn quality_advance(&self, efficiency:u32, condition_rate:u32, innovation:bool, grate_strides:bool, inner_quiet:u32) -> u32 {
+        let inner_quiet_rate = 100 + inner_quiet as u64 * 10;
+        let buff_rate = 100 + if grate_strides { 100 } else { 0 } + if innovation { 50 } else { 0 };
+
+        let base = self.base_quality * condition_rate as u64 * inner_quiet_rate / 10000;
+        ( base * efficiency as u64 * buff_rate / 10000 ) as u32
+    }
+}
+
 impl AdvanceTable for ApproximationTable {
     fn working_advance(&self, efficiency:u32, high_progress:bool, veneration:bool, muscle_memory:bool) -> u32 {
         let cond_rate = if high_progress { 1.5 } else { 1.0 };
@@ -155,7 +328,7 @@ impl AdvanceTable for ApproximationTable {
     // こちらの記事が紹介しているcalculatorの内容を参考にしています。
     // https://jp.finalfantasyxiv.com/lodestone/character/29523439/blog/4641394/
     // 完全一致はしませんが、近似値として使えます。完全一致を求めるならば、データシートを作るほうが良いと思う
-    fn quality_advance(&self, efficiency: u32, high_quality: bool, innovation: bool, grate_strides: bool, inner_quiet: u32 ) -> u32 {
+    fn quality_advance(&self, efficiency: u32, condition_rate: u32, innovation: bool, grate_strides: bool, inner_quiet: u32 ) -> u32 {
         let iq : f64 = From::from(inner_quiet);
         let process_accuracy : f64 = From::from(self.process_accuracy);
         let required_process_accuracy : f64 = From::from(self.required_process_accuracy);
@@ -164,7 +337,7 @@ impl AdvanceTable for ApproximationTable {
         let q1 = f*35.0/100.0 + 35.0;
         let q2 = q1 * (f + 10000.0) / (required_process_accuracy + 10000.0);
         let q3 = q2 * 60.0 / 100.0;
-        let cond_rate = if high_quality { 1.5 } else { 1.0 };
+        let cond_rate = condition_rate as f64 / 100.0;
         let buff_rate = 1.0 + if grate_strides { 1.0 } else { 0.0 } + if innovation { 0.5 } else { 0.0 };
 
         return ( q3 * cond_rate * efficiency as f64 * buff_rate ) as u32 / 100;
@@ -179,11 +352,59 @@ impl AdvanceTable for SimpleTable {
         return ( self.work_base as f64 * cond_rate * efficiency as f64 * buff_rate ) as u32 / 100;
     }
 
-    fn quality_advance(&self, efficiency:u32, high_quality:bool, innovation:bool, grate_strides:bool, inner_quiet:u32) -> u32 {
-        let q3 = *self.quality_base.get( &QualityKey { inner_quiet, efficiency } ).expect("undefined key") as f64;
-        let cond_rate = if high_quality { 1.5 } else { 1.0 };
+    fn quality_advance(&self, efficiency:u32, condition_rate:u32, innovation:bool, grate_strides:bool, inner_quiet:u32) -> u32 {
+        // 実測していない効率(黄金の真価など)は、同じインナークワイエットの効率100の値から比例で求めます
+        let q3 = match self.quality_base.get( &QualityKey { inner_quiet, efficiency } ) {
+            Some(x) => *x as f64,
+            None => *self.quality_base.get( &QualityKey { inner_quiet, efficiency:100 } ).expect("undefined key") as f64 * efficiency as f64 / 100.0,
+        };
+        let cond_rate = condition_rate as f64 / 100.0;
         let buff_rate = 1.0 + if grate_strides { 1.0 } else { 0.0 } + if innovation { 0.5 } else { 0.0 };
 
         return ( q3 * cond_rate * buff_rate ) as u32;
     }
 }
+
+#[test]
+fn test_formula_table()
+{
+    let crafter = CrafterStatus { craftsmanship:4000, control:4000, cp:600, level:90, specialist:false };
+    let recipe = RecipeStatus { class_job_level:90, difficulty:6600, quality:14040, durability:70, progress_divider:130, quality_divider:115, progress_modifier:90, quality_modifier:80 };
+    let table = FormulaTable::new(&crafter, &recipe);
+
+    // floor((40000/130 + 2) * 0.9) = 278, floor((40000/115 + 35) * 0.8) = 306
+    assert_eq!( 278, table.base_progress );
+    assert_eq!( 306, table.base_quality );
+
+    // 作業(120%)+ヴェネレーション: floor(278 * 1.2 * 1.5) = 500
+    assert_eq!( 500, table.working_advance(120, false, true, false) );
+
+    // 加工(100%)+IQ10+高品質+イノベ+グレスト: floor(floor(306 * 1.5 * 2.0) * 2.5) = 2295
+    assert_eq!( 2295, table.quality_advance(100, 150, true, true, 10) );
+
+    // レシピのクラスレベルを超えていれば補正は掛かりません
+    let table = FormulaTable::new(&CrafterStatus { level:91, .. crafter }, &recipe);
+    assert_eq!( 309, table.base_progress );
+}
+
+#[test]
+fn test_simple_table_fallback()
+{
+    use xorshift::SeedableRng;
+    use super::logic::{State,Action,Modifier};
+    use super::ruleset::{create_ruleset,RulesetType};
+
+    // --recipeなしで--ruleset dawntrailを指定した時の設定です。真価の効率300%は実測値にありません
+    let mut mod_param = ModifierParameter::new_fountain_of_usouso();
+    mod_param.ruleset = create_ruleset(RulesetType::Dawntrail);
+    let mut modifier = Modifier { mod_param:mod_param.clone(), rng:SeedableRng::from_seed(&[1u64,1u64][..]), forced_success:None };
+
+    let s = State::new(&mod_param);
+    assert!( s.check_action(&mod_param, &Action::Reflect) );
+    let s = s.run_action(&mut modifier, &Action::Reflect);
+    assert!( s.quality > 0 );
+
+    // 実測値がある効率はそのまま使います
+    assert_eq!( 247 * 3, mod_param.advance_table.quality_advance(300, 100, false, false, 0) );
+    assert_eq!( 308, mod_param.advance_table.quality_advance(125, 100, false, false, 0) );
+}
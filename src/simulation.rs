@@ -0,0 +1,101 @@
+use super::logic::{Action,State,Modifier};
+use super::setting::ModifierParameter;
+use super::reward::get_reward;
+use super::target::hq_percent;
+use super::seed::new_rng;
+
+// 戦略が次に取る行動です。
+pub enum Decision {
+    Run(Action),      // アクションを実行する
+    Stop,             // 手順が尽きた(未完成のまま終了)
+    Abort,            // 実行できない手があったので中断する
+}
+
+// 固定マクロや条件付きの手順など、状態を見て次の手を決めるものです。
+pub trait Strategy {
+    // 1回の製作の開始時に呼ばれます
+    fn reset(&mut self);
+
+    fn decide(&mut self, s:&State, mod_param:&ModifierParameter) -> Decision;
+}
+
+// 1回の製作の結果です。
+pub struct PlayResult {
+    pub state : State,
+    pub aborted : bool,
+}
+
+pub fn play_once(strategy:&mut dyn Strategy, initial_state:&State, modifier:&mut Modifier) -> PlayResult {
+    strategy.reset();
+
+    let mut state = initial_state.clone();
+    while !state.is_terminated() {
+        match strategy.decide(&state, &modifier.mod_param) {
+            Decision::Run(a) => state = state.run_action(modifier, &a),
+            Decision::Stop => break,
+            Decision::Abort => return PlayResult { state, aborted:true },
+        }
+    }
+    PlayResult { state, aborted:false }
+}
+
+pub fn simulate(strategy:&mut dyn Strategy, mod_param:&ModifierParameter, initial_state:&State, plays:usize, seed:u64) -> Vec<PlayResult> {
+    let mut modifier = Modifier { mod_param:mod_param.clone(), rng:new_rng(seed), forced_success:None };
+
+    (0..plays).map(|_| play_once(strategy, initial_state, &mut modifier)).collect()
+}
+
+// 集計結果と品質のヒストグラムを表示します
+pub fn print_summary(results:&[PlayResult], mod_param:&ModifierParameter) {
+    let n = results.len() as f32;
+    if results.is_empty() {
+        return
+    }
+
+    let completed : Vec<&State> = results.iter().filter(|x| !x.aborted && x.state.is_completed()).map(|x| &x.state).collect();
+    let aborted = results.iter().filter(|x| x.aborted).count();
+    let qualities : Vec<f32> = completed.iter().map(|s| s.quality as f32).collect();
+    let mean_quality = if qualities.is_empty() { 0.0 } else { qualities.iter().sum::<f32>() / qualities.len() as f32 };
+    let var_quality = if qualities.is_empty() { 0.0 } else { qualities.iter().map(|q| (q-mean_quality)*(q-mean_quality)).sum::<f32>() / qualities.len() as f32 };
+    let mean_time = if completed.is_empty() { 0.0 } else { completed.iter().map(|s| s.time as f32 / 1000.0).sum::<f32>() / completed.len() as f32 };
+    let threshold = completed.iter().filter(|s| s.quality >= mod_param.bonus_threshold).count();
+    let reward : f32 = results.iter().filter(|x| !x.aborted && x.state.is_terminated()).map(|x| get_reward(&x.state, mod_param)).sum(); // 未完成・中断は0とします
+
+    println!("plays:{}", results.len());
+    println!("completed:{:.2}% aborted:{:.2}%", completed.len() as f32 / n * 100.0, aborted as f32 / n * 100.0);
+    println!("quality(completed):mean {:.1} stddev {:.1}", mean_quality, var_quality.sqrt());
+    println!("time(completed):mean {:.1}s", mean_time);
+    println!("threshold({}):{:.2}%", mod_param.bonus_threshold, threshold as f32 / n * 100.0);
+    if let Some(scoring) = &mod_param.scoring {
+        let score : u32 = completed.iter().map(|s| scoring.score(s.quality)).sum();
+        println!("{}:{:.1}", scoring.name, score as f32 / n);
+    }
+    let hq : u32 = completed.iter().map(|s| hq_percent(s.quality, mod_param.max_quality)).sum();
+    println!("hq:{:.2}%", hq as f32 / n);
+    if let Some(collectable) = &mod_param.collectable {
+        let scrip : u32 = completed.iter().map(|s| collectable.scrip(s.quality)).sum();
+        println!("scrip:{:.1}", scrip as f32 / n);
+        for (i,tier) in collectable.tiers.iter().enumerate() {
+            let count = completed.iter().filter(|s| collectable.tier(s.quality) == Some(i)).count();
+            println!("tier{}({}):{:.2}%", i+1, tier.collectability, count as f32 / n * 100.0);
+        }
+    }
+    println!("reward:{:.4}", reward / n);
+
+    // 品質上限を10分割したヒストグラムです。未完成・中断は別枠にします
+    const BINS: usize = 10;
+    let mut hist = [0usize;BINS];
+    for q in &qualities {
+        let i = (*q as usize * BINS / (mod_param.max_quality as usize + 1)).min(BINS-1);
+        hist[i] += 1;
+    }
+
+    let max_count = hist.iter().cloned().max().unwrap_or(0).max(results.len() - completed.len()).max(1);
+    let bar = |x:usize| "#".repeat(x * 50 / max_count);
+    for (i,x) in hist.iter().enumerate() {
+        let lo = mod_param.max_quality as usize * i / BINS;
+        let hi = mod_param.max_quality as usize * (i+1) / BINS;
+        println!("{:>6}-{:<6}|{:>7} {}", lo, hi, x, bar(*x));
+    }
+    println!("{:>13}|{:>7} {}", "failed", results.len() - completed.len(), bar(results.len() - completed.len()));
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use num::FromPrimitive;
+use xorshift::SeedableRng;
+
+use super::logic::{State,Action,Modifier,ACTION_NUM};
+use super::setting::ModifierParameter;
+use super::reward::get_reward;
+
+pub struct SolverParameter {
+    pub mod_param : ModifierParameter,
+    pub initial_state : State,
+    pub max_turn : u32,
+}
+
+// 全ての分岐を厳密に展開して期待値最大の手を求めます。
+// 状態数が爆発するので、耐久の少ないレシピや終盤の局面向けです
+pub struct Solver
+{
+    modifier : Modifier,
+
+    // このターンを超えても終わらない場合は失敗として扱います
+    max_turn : u32,
+
+    // 状態ごとの期待報酬と最善手
+    memo : HashMap<State,(f32,Option<Action>)>,
+}
+
+impl Solver {
+
+    pub fn new(mod_param:&ModifierParameter, max_turn:u32) -> Solver {
+        // 成否は列挙するので乱数は使いません
+        let seeds = [1u64, 1u64];
+        Solver {
+            modifier : Modifier { mod_param:mod_param.clone(), rng:SeedableRng::from_seed(&seeds[..]), forced_success:None },
+            max_turn : max_turn,
+            memo : HashMap::new(),
+        }
+    }
+
+    pub fn memo_size(&self) -> usize {
+        self.memo.len()
+    }
+
+    // アクションを取った時の期待報酬です
+    pub fn q_value(&mut self, s:&State, a:&Action) -> f32 {
+        s.outcomes(&mut self.modifier, a).iter().map(|(ns,p)| p * self.solve(ns).0).sum()
+    }
+
+    // 状態sの期待報酬と最善手を返します
+    pub fn solve(&mut self, s:&State) -> (f32,Option<Action>) {
+        if s.is_terminated() {
+            return (get_reward(s, &self.modifier.mod_param), None)
+        }
+        if s.turn > self.max_turn {
+            return (0.0, None)
+        }
+        if let Some(x) = self.memo.get(s) {
+            return *x
+        }
+
+        let mut best = (0.0, None);
+        for i in 0..ACTION_NUM {
+            let a = Action::from_usize(i).unwrap();
+            if s.check_action(&self.modifier.mod_param, &a) {
+                let q = self.q_value(s, &a);
+                if best.1.is_none() || q > best.0 {
+                    best = (q, Some(a));
+                }
+            }
+        }
+
+        self.memo.insert(s.clone(), best);
+        best
+    }
+
+    // 最善手を取り、最も確率の高い分岐を辿った手順(ターンと手)と最後の状態です
+    // 確率が同じ分岐はoutcomesの順(成功が先)で最初のものを選びます
+    pub fn principal_variation(&mut self, s:&State) -> (Vec<(u32,Action)>,State) {
+        let mut s = s.clone();
+        let mut pv = vec![];
+        while let (_,Some(a)) = self.solve(&s) {
+            pv.push((s.turn, a));
+            let outcomes = s.outcomes(&mut self.modifier, &a);
+            s = outcomes.into_iter().fold(None, |best:Option<(State,f32)>,x| match best {
+                Some(b) if b.1.total_cmp(&x.1).is_ge() => Some(b),
+                _ => Some(x),
+            }).unwrap().0;
+        }
+        (pv, s)
+    }
+}
+
+// 初期状態の各手の期待報酬と、最も確率の高い分岐を辿った最善手順を表示します
+pub fn run_solver( param:SolverParameter ) {
+    let mut solver = Solver::new(&param.mod_param, param.max_turn);
+    let s = param.initial_state.clone();
+
+    let (value,_) = solver.solve(&s);
+    println!("Expected reward:{} (states:{})", value, solver.memo_size());
+
+    let mut qs = vec![];
+    for i in 0..ACTION_NUM {
+        let a = Action::from_usize(i).unwrap();
+        if s.check_action(&param.mod_param, &a) {
+            qs.push((a, solver.q_value(&s, &a)));
+        }
+    }
+    qs.sort_by(|x,y| y.1.total_cmp(&x.1));
+    for (a,q) in qs {
+        println!("{:?}:{}", a, q);
+    }
+
+    let (pv,last) = solver.principal_variation(&s);
+    for (turn,a) in pv {
+        println!("turn:{} {:?}", turn, a);
+    }
+    println!("quality:{} working:{} completed:{}", last.quality, last.working, last.is_completed());
+}
+
+#[test]
+fn test_solver_terminal()
+{
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let mut solver = Solver::new(&mod_param, 5);
+
+    // 耐久が尽きた状態は報酬0です
+    let s = State { durability:0, .. State::new(&mod_param) };
+    assert_eq!( (0.0,None), solver.solve(&s) );
+
+    // 最終ターンで作業すれば完成する状態です
+    let s = State { turn:5, working:mod_param.max_working - 1, durability:10, cp:0, .. State::new(&mod_param) };
+    let (value,action) = solver.solve(&s);
+    assert!( value > 0.0 );
+    assert!( action.is_some() );
+}
+
+#[test]
+fn test_solver_expected_value()
+{
+    use super::condition::ConditionModel;
+    use super::logic::Condition;
+    use super::reward::{create_reward_model,RewardModelType};
+
+    // 状態は通常だけ、報酬は完成したかどうかだけにします
+    let mut mod_param = ModifierParameter::new_fountain_of_usouso();
+    mod_param.condition_model = ConditionModel::Independent(vec![(Condition::Standard, 1.0)]);
+    mod_param.reward_model = create_reward_model(RewardModelType::Completion);
+
+    // 残り2ターンで、高速作業(成功率50%)でしか完成できない状態です
+    let rest = mod_param.advance_table.working_advance(400, false, false, false);
+    let s = State { turn:2, working:mod_param.max_working - rest, durability:20, cp:0, .. State::new(&mod_param) };
+    let mut solver = Solver::new(&mod_param, 3);
+
+    // 高速作業を2回試せるので 0.5 + 0.5 * 0.5 です
+    assert_eq!( (0.75, Some(Action::RapidSynthesis)), solver.solve(&s) );
+    assert_eq!( 0.5, solver.q_value(&s, &Action::BasicSynthesis) );
+
+    // 成否が五分でも成功の分岐を辿ります
+    let (pv,last) = solver.principal_variation(&s);
+    assert_eq!( vec![(2, Action::RapidSynthesis)], pv );
+    assert!( last.is_completed() );
+}
@@ -0,0 +1,107 @@
+use serde::{Serialize,Deserialize};
+
+use super::logic::State;
+use super::setting::ModifierParameter;
+
+// 素材です。HQ素材の数に応じて初期品質が決まります
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct Ingredient
+{
+    pub level : u32,                      // アイテムレベル
+    pub amount : u32,                     // 必要数
+    #[serde(default)]
+    pub hq_amount : u32,                  // うちHQの数
+}
+
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct Materials
+{
+    #[serde(default="default_quality_factor")]
+    pub quality_factor : u32,             // 全てHQの時に品質上限の何%になるか
+    pub ingredients : Vec<Ingredient>,
+}
+
+// 開始状態の設定です。
+// 素材から初期品質を計算し、stateに書いた項目はそのまま上書きします
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct StartSetting
+{
+    #[serde(default)]
+    pub materials : Option<Materials>,
+
+    // Stateの一部の項目だけを書けます。例: {"turn":9,"working":3000,"inner_quiet":6,"innovation":2}
+    #[serde(default)]
+    pub state : Option<serde_json::Value>,
+}
+
+fn default_quality_factor() -> u32 { 50 }
+
+impl Materials {
+
+    // floor(品質上限 * quality_factor% * HQ素材のレベル合計 / 素材のレベル合計)
+    pub fn initial_quality(&self, max_quality:u32) -> u32 {
+        let total : u64 = self.ingredients.iter().map(|x| (x.level * x.amount) as u64).sum();
+        let hq : u64 = self.ingredients.iter().map(|x| (x.level * std::cmp::min(x.hq_amount, x.amount)) as u64).sum();
+
+        if total == 0 {
+            0
+        }
+        else {
+            (max_quality as u64 * self.quality_factor as u64 * hq / (total * 100)) as u32
+        }
+    }
+}
+
+impl StartSetting {
+
+    pub fn load(path:&str) -> Result<StartSetting, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        serde_json::from_str(&text).map_err(|e| format!("can't parse {}: {}", path, e))
+    }
+
+    pub fn build(&self, mod_param:&ModifierParameter) -> Result<State, String> {
+        let mut state = State::new(mod_param);
+
+        if let Some(materials) = &self.materials {
+            state.quality = std::cmp::min(materials.initial_quality(mod_param.max_quality), mod_param.max_quality);
+        }
+
+        // 書かれていない項目は既定値のままにするため、JSON上でマージしてから戻します
+        if let Some(serde_json::Value::Object(fields)) = &self.state {
+            let mut value = serde_json::to_value(&state).map_err(|e| e.to_string())?;
+            if let serde_json::Value::Object(base) = &mut value {
+                for (k,v) in fields {
+                    if !base.contains_key(k) {
+                        return Err(format!("unknown state field {}", k))
+                    }
+                    base.insert(k.clone(), v.clone());
+                }
+            }
+            state = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        }
+        else if self.state.is_some() {
+            return Err("state must be a JSON object".to_string())
+        }
+
+        Ok(state)
+    }
+}
+
+#[test]
+fn test_start_setting()
+{
+    let mod_param = ModifierParameter::new_fountain_of_usouso();
+    let setting : StartSetting = serde_json::from_str(r#"{
+        "materials": { "ingredients": [ { "level":560, "amount":3, "hq_amount":3 }, { "level":560, "amount":3 } ] },
+        "state": { "turn":9, "working":3000, "inner_quiet":6, "innovation":2 }
+    }"#).unwrap();
+    let s = setting.build(&mod_param).unwrap();
+
+    // 13620 * 50% * 半分HQ
+    assert_eq!( 3405, s.quality );
+    assert_eq!( 9, s.turn );
+    assert_eq!( 3000, s.working );
+    assert_eq!( 6, s.inner_quiet );
+    assert_eq!( 2, s.innovation );
+    assert_eq!( mod_param.max_cp, s.cp );
+}
@@ -0,0 +1,161 @@
+use serde::{Serialize,Deserialize};
+
+// 品質(%)からHQ率(%)への変換表です
+const HQ_PERCENT_TABLE: [u32;101] = [
+    1, 1, 1, 1, 1, 2, 2, 2, 2, 3,
+    3, 3, 3, 4, 4, 4, 4, 5, 5, 5,
+    5, 6, 6, 6, 6, 7, 7, 7, 7, 8,
+    8, 8, 9, 9, 9, 10, 10, 10, 11, 11,
+    11, 12, 12, 12, 13, 13, 13, 14, 14, 14,
+    15, 15, 15, 16, 16, 17, 17, 17, 18, 18,
+    18, 19, 19, 20, 20, 21, 22, 23, 24, 26,
+    28, 31, 34, 38, 42, 47, 52, 58, 64, 68,
+    71, 74, 76, 78, 80, 81, 82, 83, 84, 85,
+    86, 87, 88, 89, 90, 91, 92, 94, 96, 98,
+    100,
+];
+
+// 品質からHQ率(%)を求めます
+pub fn hq_percent(quality:u32, max_quality:u32) -> u32 {
+    if max_quality == 0 {
+        return 0
+    }
+    let percent = std::cmp::min(quality as u64 * 100 / max_quality as u64, 100);
+    HQ_PERCENT_TABLE[percent as usize]
+}
+
+// 収集価値の段階です
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct CollectableTier
+{
+    pub collectability : u32,             // 必要な収集価値
+    pub scrip : u32,                      // 納品時のスクリップ
+}
+
+// 収集品の設定です。段階は収集価値の昇順に並べます
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct Collectable
+{
+    pub tiers : Vec<CollectableTier>,
+}
+
+impl Collectable {
+
+    // 収集価値は品質の1/10です
+    pub fn collectability(quality:u32) -> u32 {
+        quality / 10
+    }
+
+    // 到達した段階です。最低段階に届かなければNone
+    pub fn tier(&self, quality:u32) -> Option<usize> {
+        let collectability = Collectable::collectability(quality);
+        self.tiers.iter().rposition(|t| collectability >= t.collectability)
+    }
+
+    pub fn scrip(&self, quality:u32) -> u32 {
+        self.tier(quality).map_or(0, |i| self.tiers[i].scrip)
+    }
+
+    // 最高段階のスクリップを1とした報酬です
+    pub fn reward(&self, quality:u32) -> f32 {
+        let max_scrip = self.tiers.iter().map(|t| t.scrip).max().unwrap_or(0);
+        if max_scrip == 0 {
+            0.0
+        }
+        else {
+            self.scrip(quality) as f32 / max_scrip as f32
+        }
+    }
+}
+
+// スコア計算の入力値です
+#[derive(Debug,Copy,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(rename_all="lowercase")]
+pub enum ScoreInput {
+    Quality,          // 品質
+    Collectability,   // 収集価値(品質の1/10)
+}
+
+impl Default for ScoreInput {
+    fn default() -> Self { ScoreInput::Collectability }
+}
+
+// 区分線形の1区間です。入力値がfrom以上の時に (slope*入力値 + intercept) / divisor を使います
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct ScoreSegment
+{
+    pub from : u32,
+    #[serde(default)]
+    pub slope : i64,
+    #[serde(default)]
+    pub intercept : i64,
+    #[serde(default="default_divisor")]
+    pub divisor : i64,
+}
+
+fn default_divisor() -> i64 { 1 }
+
+// 納品イベントのスコア表です。区間はfromの昇順に並べ、最初の区間未満は0点です
+// 段階制のスクリップはslopeを0にしてinterceptに点数を書きます
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct Scoring
+{
+    pub name : String,                    // 表示名(例: 技術点)
+    #[serde(default)]
+    pub input : ScoreInput,
+    pub segments : Vec<ScoreSegment>,
+}
+
+impl Scoring {
+
+    // 蒼天街復興の技術点です
+    // Velvet Weissmelさんの調査式を元に計算
+    // https://jp.finalfantasyxiv.com/lodestone/character/3514261/blog/4645845/
+    pub fn new_ishgard_restoration() -> Scoring {
+        Scoring {
+            name : "Technical Point".to_string(),
+            input : ScoreInput::Collectability,
+            segments : vec![
+                ScoreSegment { from:5800, slope:1, intercept:-4050, divisor:10 },
+                ScoreSegment { from:6500, slope:3, intercept:-15800, divisor:10 },
+                ScoreSegment { from:7400, slope:6, intercept:-36200, divisor:10 },
+            ],
+        }
+    }
+
+    pub fn score(&self, quality:u32) -> u32 {
+        let x = match self.input {
+            ScoreInput::Quality => quality,
+            ScoreInput::Collectability => Collectable::collectability(quality),
+        };
+
+        match self.segments.iter().rev().find(|seg| x >= seg.from) {
+            Some(seg) if seg.divisor != 0 => std::cmp::max((seg.slope * x as i64 + seg.intercept) / seg.divisor, 0) as u32,
+            _ => 0,
+        }
+    }
+}
+
+#[test]
+fn test_target() {
+    assert_eq!(hq_percent(0, 10000), 1);
+    assert_eq!(hq_percent(7500, 10000), 47);
+    assert_eq!(hq_percent(10000, 10000), 100);
+    assert_eq!(hq_percent(20000, 10000), 100);
+
+    let collectable = Collectable { tiers: vec![
+        CollectableTier { collectability:500, scrip:100 },
+        CollectableTier { collectability:800, scrip:150 },
+        CollectableTier { collectability:1000, scrip:200 },
+    ]};
+    assert_eq!(collectable.tier(4999), None);
+    assert_eq!(collectable.tier(8000), Some(1));
+    assert_eq!(collectable.scrip(12000), 200);
+    assert_eq!(collectable.reward(5000), 0.5);
+
+    let scoring = Scoring::new_ishgard_restoration();
+    assert_eq!(scoring.score(57990), 0);
+    assert_eq!(scoring.score(60000), 195);
+    assert_eq!(scoring.score(70000), 520);
+    assert_eq!(scoring.score(80000), 1180);
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use serde::{Serialize,Deserialize};
+
+use super::logic::Action;
+
+// アクションの所要時間と時間ボーナスの設定です。
+// 時間の単位は全て秒で、Stateの経過時間はミリ秒で積算します
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct TimingModel
+{
+    #[serde(default="default_action_wait")]
+    pub action_wait : f32,                // 通常アクションの待ち時間
+    #[serde(default="default_buff_wait")]
+    pub buff_wait : f32,                  // バフ系アクションの待ち時間
+    #[serde(default)]
+    pub latency : f32,                    // 1アクションごとに加わる通信等の遅延
+    #[serde(default)]
+    pub waits : HashMap<Action,f32>,      // アクション個別の待ち時間(上の設定より優先)
+    #[serde(default="default_best_time")]
+    pub best_time : f32,                  // これ以下なら時間報酬1
+    #[serde(default="default_worst_time")]
+    pub worst_time : f32,                 // これ以上なら時間報酬0
+}
+
+fn default_action_wait() -> f32 { 3.0 }
+fn default_buff_wait() -> f32 { 2.0 }
+fn default_best_time() -> f32 { 50.0 }
+fn default_worst_time() -> f32 { 150.0 }
+
+impl Default for TimingModel {
+    fn default() -> Self {
+        TimingModel {
+            action_wait : default_action_wait(),
+            buff_wait : default_buff_wait(),
+            latency : 0.0,
+            waits : HashMap::new(),
+            best_time : default_best_time(),
+            worst_time : default_worst_time(),
+        }
+    }
+}
+
+impl TimingModel {
+
+    pub fn load(path:&str) -> Result<TimingModel, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        serde_json::from_str(&text).map_err(|e| format!("can't parse {}: {}", path, e))
+    }
+
+    // 遅延込みの所要時間です(秒)
+    pub fn duration(&self, a:&Action) -> f32 {
+        let wait = match self.waits.get(a) {
+            Some(x) => *x,
+            None => if a.is_buff() { self.buff_wait } else { self.action_wait },
+        };
+        wait + self.latency
+    }
+
+    // Stateに積算する所要時間です(ミリ秒)
+    pub fn duration_ms(&self, a:&Action) -> u32 {
+        (self.duration(a) * 1000.0).round() as u32
+    }
+
+    // 経過時間(ミリ秒)を[0,1]の時間報酬にします
+    pub fn time_reward(&self, time_ms:u32) -> f32 {
+        let t = time_ms as f32 / 1000.0;
+        if self.worst_time <= self.best_time {
+            return if t <= self.best_time { 1.0 } else { 0.0 }
+        }
+        ((self.worst_time - t) / (self.worst_time - self.best_time)).max(0.0).min(1.0)
+    }
+}
+
+#[test]
+fn test_timing_model() {
+    let timing : TimingModel = serde_json::from_str(r#"{"latency":0.5,"waits":{"Observe":2.5},"best_time":40,"worst_time":60}"#).unwrap();
+
+    assert_eq!(timing.duration_ms(&Action::BasicSynthesis), 3500);
+    assert_eq!(timing.duration_ms(&Action::Innovation), 2500);
+    assert_eq!(timing.duration_ms(&Action::Observe), 3000);
+
+    assert_eq!(timing.time_reward(30000), 1.0);
+    assert_eq!(timing.time_reward(50000), 0.5);
+    assert_eq!(timing.time_reward(70000), 0.0);
+}
@@ -69,7 +69,7 @@ fn aggregate_records( records:&Vec<Record> ) -> BTreeMap<String,(f64,usize)> {
 fn write_record_flush_buffer( mysql_pool:&Arc<Mutex<Pool>>, buf:&Vec<Record> ) {
     // リプレイデータの打ち上げ
     {
-        let encoded: Vec<u8> = bincode::serialize(&buf).unwrap();
+        let encoded: Vec<u8> = encode_records(&buf);
 
         {
             let file = std::fs::File::create("record.bincode.bz2").unwrap();